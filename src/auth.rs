@@ -0,0 +1,215 @@
+use std::{cell::RefCell, env, path::PathBuf, rc::Rc};
+use git2::{Cred, CredentialType, ErrorClass, ErrorCode};
+
+/// One way of answering a credentials request from libgit2.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Method {
+    SshAgent,                                  // Keys loaded in the running ssh-agent
+    SshKey(PathBuf),                           // An explicit or default private key file
+    Helper,                                    // The git credential helpers from gitconfig
+    Username,                                  // Bare username, requested before SSH auth
+    Default,                                   // Platform default (e.g. NTLM/Negotiate)
+}
+
+impl Method {
+    /// Human-readable name used in error reports.
+    pub fn describe(&self) -> String {
+        match self {
+            Method::SshAgent => "ssh-agent".into(),
+            Method::SshKey(path) => format!("key file '{}'", path.display()),
+            Method::Helper => "git credential helper".into(),
+            Method::Username => "username".into(),
+            Method::Default => "default credentials".into(),
+        }
+    }
+}
+
+/// Decides which credential method to offer next.
+/// libgit2 calls the credentials callback again after every rejected attempt,
+/// so each method is offered at most once, in this order:
+/// ssh-agent, configured key files (repository, then global), default `~/.ssh`
+/// keys, then git credential helpers for HTTPS.
+#[derive(Debug)]
+pub struct CredentialChain {
+    keys: Vec<PathBuf>,
+    defaults: Vec<PathBuf>,                    // Default `~/.ssh` keys found when the chain was made
+    tried: Vec<Method>,
+}
+
+impl CredentialChain {
+    /// Creates a chain offering `keys` before the default `~/.ssh` keys.
+    pub fn new(keys: Vec<PathBuf>) -> Self {
+        Self { keys, defaults: default_keys(), tried: Vec::new() }
+    }
+
+    /// Returns the next untried method compatible with `allowed`.
+    pub fn next(&mut self, allowed: CredentialType) -> Option<Method> {
+        let mut candidates = Vec::new();
+
+        if allowed.contains(CredentialType::USERNAME) {
+            candidates.push(Method::Username);
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            candidates.push(Method::SshAgent);
+            candidates.extend(self.keys.iter().cloned().map(Method::SshKey));
+            candidates.extend(self.defaults.iter().cloned().map(Method::SshKey));
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            candidates.push(Method::Helper);
+        }
+
+        if allowed.contains(CredentialType::DEFAULT) {
+            candidates.push(Method::Default);
+        }
+
+        let next = candidates.into_iter().find(|m| !self.tried.contains(m))?;
+        self.tried.push(next.clone());
+        Some(next)
+    }
+
+    /// Methods offered so far, excluding the bare username step.
+    pub fn tried(&self) -> Vec<String> {
+        self.tried.iter()
+            .filter(|m| **m != Method::Username)
+            .map(Method::describe)
+            .collect()
+    }
+}
+
+/// Returns the default private keys that exist in `~/.ssh`.
+fn default_keys() -> Vec<PathBuf> {
+    let Some(home) = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) else {
+        return Vec::new();
+    };
+    let ssh = PathBuf::from(home).join(".ssh");
+
+    ["id_ed25519", "id_ecdsa", "id_rsa"].iter()
+        .map(|k| ssh.join(k))
+        .filter(|k| k.exists())
+        .collect()
+}
+
+/// Shared handle on a chain, so a failed fetch can report what was tried.
+pub type SharedChain = Rc<RefCell<CredentialChain>>;
+
+/// Answers a libgit2 credentials request using the next method of the chain.
+pub fn credentials(chain: &SharedChain, url: &str, username: Option<&str>, allowed: CredentialType) -> Result<Cred, git2::Error> {
+    let user = username.unwrap_or("git");
+
+    loop {
+        let Some(method) = chain.borrow_mut().next(allowed) else {
+            return Err(git2::Error::from_str("no more credentials to try"));
+        };
+
+        let cred = match &method {
+            Method::Username => Cred::username(user),
+            Method::SshAgent => Cred::ssh_key_from_agent(user),
+            Method::SshKey(path) => Cred::ssh_key(user, None, path, None),
+            Method::Helper => git2::Config::open_default()
+                .and_then(|cfg| Cred::credential_helper(&cfg, url, username)),
+            Method::Default => Cred::default(),
+        };
+
+        // A method that can't even produce credentials (no agent, no helper
+        // entry) is skipped straight away instead of failing the fetch.
+        if cred.is_ok() {
+            return cred;
+        }
+    }
+}
+
+/// Rewrites an authentication failure into a report of every method tried.
+pub fn explain(err: git2::Error, chain: &SharedChain) -> git2::Error {
+    let tried = chain.borrow().tried();
+    let auth_failure = err.code() == ErrorCode::Auth
+        || matches!(err.class(), ErrorClass::Ssh | ErrorClass::Http)
+        || err.message().contains("no more credentials");
+
+    if tried.is_empty() || !auth_failure {
+        return err;
+    }
+
+    git2::Error::from_str(&format!(
+        "authentication failed ({}). Tried: {}. Configure a key with 'lubig auth <name|--global> <key_file>' or a git credential helper.",
+        err.message(), tried.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chain with a repository key, a global key and one default `~/.ssh` key.
+    fn chain() -> CredentialChain {
+        CredentialChain {
+            keys: vec![PathBuf::from("/keys/repo"), PathBuf::from("/keys/global")],
+            defaults: vec![PathBuf::from("/home/u/.ssh/id_ed25519")],
+            tried: Vec::new(),
+        }
+    }
+
+    fn drain(chain: &mut CredentialChain, allowed: CredentialType) -> Vec<Method> {
+        std::iter::from_fn(|| chain.next(allowed)).collect()
+    }
+
+    #[test]
+    fn offers_methods_in_order() {
+        let allowed = CredentialType::USERNAME | CredentialType::SSH_KEY | CredentialType::USER_PASS_PLAINTEXT;
+
+        assert_eq!(drain(&mut chain(), allowed), vec![
+            Method::Username,
+            Method::SshAgent,
+            Method::SshKey(PathBuf::from("/keys/repo")),
+            Method::SshKey(PathBuf::from("/keys/global")),
+            Method::SshKey(PathBuf::from("/home/u/.ssh/id_ed25519")),
+            Method::Helper,
+        ]);
+    }
+
+    #[test]
+    fn offers_each_method_once() {
+        let mut chain = chain();
+        assert_eq!(chain.next(CredentialType::SSH_KEY), Some(Method::SshAgent));
+
+        // libgit2 asks again with a wider set: the agent is not offered twice.
+        let rest = drain(&mut chain, CredentialType::SSH_KEY | CredentialType::USER_PASS_PLAINTEXT);
+        assert!(!rest.contains(&Method::SshAgent));
+        assert_eq!(rest.len(), 4);
+        assert_eq!(chain.next(CredentialType::all()), Some(Method::Username));
+        assert_eq!(chain.next(CredentialType::all()), Some(Method::Default));
+        assert_eq!(chain.next(CredentialType::all()), None);
+    }
+
+    #[test]
+    fn filters_by_allowed_types() {
+        assert_eq!(drain(&mut chain(), CredentialType::USER_PASS_PLAINTEXT), vec![Method::Helper]);
+        assert_eq!(drain(&mut chain(), CredentialType::DEFAULT), vec![Method::Default]);
+        assert!(drain(&mut chain(), CredentialType::SSH_MEMORY).is_empty());
+    }
+
+    #[test]
+    fn tried_excludes_username() {
+        let mut chain = chain();
+        drain(&mut chain, CredentialType::USERNAME | CredentialType::USER_PASS_PLAINTEXT);
+
+        assert_eq!(chain.tried(), vec!["git credential helper".to_string()]);
+    }
+
+    #[test]
+    fn explain_rewrites_only_auth_errors() {
+        let shared: SharedChain = Rc::new(RefCell::new(chain()));
+        let auth = || git2::Error::new(ErrorCode::Auth, ErrorClass::Ssh, "rejected");
+        let other = || git2::Error::new(ErrorCode::NotFound, ErrorClass::Reference, "no such ref");
+
+        // Nothing tried yet: nothing to report.
+        assert_eq!(explain(auth(), &shared).message(), "rejected");
+
+        shared.borrow_mut().next(CredentialType::SSH_KEY);
+        let report = explain(auth(), &shared);
+        assert!(report.message().starts_with("authentication failed (rejected). Tried: ssh-agent."));
+        assert_eq!(explain(other(), &shared).message(), "no such ref");
+        assert!(explain(git2::Error::from_str("no more credentials to try"), &shared).message().contains("Tried: ssh-agent"));
+    }
+}
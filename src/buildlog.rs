@@ -0,0 +1,228 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+use crate::conf;
+use crate::util::LocalStuff;
+
+/// Separates the header of a build log from the script output.
+const SEPARATOR: &str = "----";
+
+/// Context written at the top of every build log.
+#[derive(Debug, Clone, Default)]
+pub struct Header {
+    pub name: String,
+    pub commit: Option<String>,                // Source commit being built
+    pub profile_hash: Option<String>,          // Git blob hash of the profile script
+}
+
+/// A build log found on disk, with the fields `lubig logs` shows.
+#[derive(Debug)]
+pub struct LogEntry {
+    pub path: PathBuf,
+    pub started: u64,                          // Unix timestamp, also the file name
+    pub seq: u32,                              // Suffix of later logs started in the same second
+    pub result: String,                        // `success` or `failed (...)`
+}
+
+impl LogEntry {
+    /// Whether the logged build failed.
+    pub fn failed(&self) -> bool {
+        self.result != "success"
+    }
+}
+
+/// A build log being written: the script output is streamed to a temporary file,
+/// and the header is added by `finish` once the build (including installation) is over.
+#[derive(Debug)]
+pub struct BuildLog {
+    header: Header,
+    path: PathBuf,
+    partial: PathBuf,
+    started: u64,
+    clock: Instant,
+}
+
+impl BuildLog {
+    /// Reserves a new log file for a build of `header.name`.
+    /// The name is `<timestamp>.log`, or `<timestamp>-<n>.log` when another build
+    /// already took that second.
+    fn create(header: &Header) -> io::Result<(BuildLog, fs::File)> {
+        let dir = conf::log_dir(&header.name);
+        LocalStuff::generate_path(&dir)?;
+        let started = LocalStuff::timestamp();
+
+        for seq in 1.. {
+            let stem = if seq == 1 { started.to_string() } else { format!("{}-{}", started, seq) };
+            let path = dir.join(format!("{}.log", stem));
+            let partial = dir.join(format!("{}.log.partial", stem));
+            if path.exists() {
+                continue;
+            }
+
+            match fs::OpenOptions::new().write(true).create_new(true).open(&partial) {
+                Ok(file) => {
+                    return Ok((BuildLog { header: header.clone(), path, partial, started, clock: Instant::now() }, file));
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!("log sequence numbers are unbounded")
+    }
+
+    /// Path the finished log is written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the header (commit, profile hash, duration and result) in front of the
+    /// recorded output and returns the log path.
+    pub fn finish(self, outcome: &Result<(), String>) -> io::Result<PathBuf> {
+        let result = match outcome {
+            Ok(()) => "success".to_string(),
+            Err(reason) => format!("failed ({})", reason),
+        };
+        let head = format!(
+            "# lubig build log\nrepository: {}\nstarted:    {}\ncommit:     {}\nprofile:    {}\nduration:   {:.2}s\nresult:     {}\n{}\n",
+            self.header.name,
+            LocalStuff::format_timestamp(self.started),
+            self.header.commit.as_deref().unwrap_or("unknown"),
+            self.header.profile_hash.as_deref().unwrap_or("unknown"),
+            self.clock.elapsed().as_secs_f64(),
+            result,
+            SEPARATOR,
+        );
+
+        let output = fs::read_to_string(&self.partial).unwrap_or_default();
+        LocalStuff::write_atomic(&self.path, &(head + &output))?;
+        fs::remove_file(&self.partial).ok();
+        Ok(self.path)
+    }
+}
+
+/// Runs the build steps in order, echoing their output to the terminal while recording it
+/// in `logs/<name>/<timestamp>.log`. Stops at the first step that fails.
+/// Output is streamed to a temporary file so nothing is lost if lubig is interrupted;
+/// the caller completes the log with `BuildLog::finish` once it knows the final result.
+/// Returns why the steps failed, if they did, and the open log.
+pub fn run(steps: Vec<Command>, header: &Header) -> io::Result<(Result<(), String>, BuildLog)> {
+    let (log, file) = BuildLog::create(header)?;
+    let body = Arc::new(Mutex::new(file));
+
+    let multi = steps.len() > 1;
+    let mut outcome = Ok(());
+
+    for mut command in steps {
+        // Name each step when there are several, so the log shows where output comes from.
+        if multi {
+            let line = format!("$ {}", describe(&command));
+            println!("{}", line);
+            if let Ok(mut file) = body.lock() {
+                writeln!(file, "{}", line).ok();
+            }
+        }
+
+        let status = match command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+            Ok(mut child) => {
+                // Tee both streams line by line; each keeps going to its own terminal stream.
+                let out = child.stdout.take().map(|s| tee(s, body.clone(), false));
+                let err = child.stderr.take().map(|s| tee(s, body.clone(), true));
+                let status = child.wait();
+                out.into_iter().chain(err).for_each(|t| { t.join().ok(); });
+                status
+            }
+            Err(e) => Err(io::Error::new(e.kind(), format!("'{}' could not be started: {}", command.get_program().to_string_lossy(), e))),
+        };
+
+        outcome = match status {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(status.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if outcome.is_err() {
+            break;
+        }
+    }
+
+    Ok((outcome, log))
+}
+
+/// Renders a command line for the log.
+fn describe(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Copies `stream` to the terminal and to `log`, one line at a time.
+fn tee<R: Read + Send + 'static>(stream: R, log: Arc<Mutex<fs::File>>, stderr: bool) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            if stderr {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+
+            if let Ok(mut file) = log.lock() {
+                writeln!(file, "{}", line).ok();
+            }
+        }
+    })
+}
+
+/// Lists the build logs of a repository, oldest first.
+pub fn list(name: &str) -> Vec<LogEntry> {
+    let Ok(dir) = fs::read_dir(conf::log_dir(name)) else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<LogEntry> = dir
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter_map(|path| {
+            let stem = path.file_name()?.to_str()?.strip_suffix(".log")?;
+            let (started, seq) = match stem.split_once('-') {
+                Some((started, seq)) => (started.parse().ok()?, seq.parse().ok()?),
+                None => (stem.parse().ok()?, 1),
+            };
+            Some(LogEntry { result: read_result(&path), path, started, seq })
+        })
+        .collect();
+
+    entries.sort_by_key(|e| (e.started, e.seq));
+    entries
+}
+
+/// Reads the `result:` line from a log header.
+fn read_result(path: &Path) -> String {
+    let Ok(file) = fs::File::open(path) else {
+        return "unreadable".into();
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .take_while(|line| line != SEPARATOR)
+        .find_map(|line| line.strip_prefix("result:").map(|r| r.trim().to_string()))
+        .unwrap_or_else(|| "unknown".into())
+}
+
+/// Deletes the oldest logs of a repository, keeping the `keep` most recent ones.
+pub fn prune(name: &str, keep: usize) {
+    let entries = list(name);
+    let excess = entries.len().saturating_sub(keep);
+
+    for entry in &entries[..excess] {
+        fs::remove_file(&entry.path).ok();
+    }
+}
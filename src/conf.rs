@@ -1,23 +1,38 @@
 use serde::{Deserialize, Serialize};
-use std::{env, fs, path::PathBuf};
-use std::collections::BTreeMap;
+use std::{cell::Cell, env, fs, path::{Path, PathBuf}, sync::OnceLock};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::text::Text;
+use crate::util::{Access, LocalStuff};
+
+/// Current layout version of `config.toml`.
+/// Files without a `schema_version` field are treated as version 1 (LUBIG 1.0.0).
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Migration chain: `MIGRATIONS[n]` upgrades a table from version `n + 1` to `n + 2`.
+const MIGRATIONS: [fn(&mut toml::Table); 1] = [migrate_v1_to_v2];
+
+/// Maximum number of pre-upgrade commits kept per repository for `rollback`.
+pub const HISTORY_LIMIT: usize = 20;
 
 /// Main configuration structure for LUBIG.
-/// Stores directory paths and registry maps for repositories.
+/// Stores directory paths and one record per registered repository.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default = "schema_default")]
+    pub schema_version: u32,                   // Layout version of this file
     #[serde(default)]
     pub directories: Directories,              // Paths for sources, profiles, and programs
     #[serde(default)]
-    pub added: BTreeMap<String, String>,       // Registered repositories
-    #[serde(default)]
-    pub unlocked: BTreeMap<String, String>,    // Unlocked repos for updates (with branch)
+    pub builds: Builds,                        // Build generation settings
+    #[serde(default, skip_serializing_if = "Auth::is_empty")]
+    pub auth: Auth,                            // Credentials offered to every remote
     #[serde(default)]
-    pub build: BTreeMap<String, String>,       // Built repos and their output paths
+    pub repos: BTreeMap<String, Repo>,         // Registered repositories, keyed by name
 }
 
 /// Directory paths used by LUBIG.
-/// Defaults are relative to the executable location.
+/// Defaults are relative to the workspace data directory (see `home`).
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Directories {
     pub sources: Option<String>,
@@ -25,126 +40,606 @@ pub struct Directories {
     pub programs: Option<String>,
 }
 
-impl Default for Directories {
+/// Build generation settings.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Builds {
+    #[serde(default = "keep_default")]
+    pub keep: usize,                           // Generations kept per program; older ones are pruned
+    #[serde(default = "logs_default")]
+    pub logs: usize,                           // Build logs kept per repository
+}
+
+fn keep_default() -> usize {
+    3
+}
+
+fn logs_default() -> usize {
+    10
+}
+
+impl Default for Builds {
     fn default() -> Self {
-        Self {
-            sources: Some(exe_path("sources").into()),
-            profiles: Some(exe_path("profiles").into()),
-            programs: Some(exe_path("programs").into()),
-        }
+        Self { keep: keep_default(), logs: logs_default() }
+    }
+}
+
+/// One installed build of a repository, in `programs/<name>/<id>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Generation {
+    pub id: String,                            // Directory name: `<timestamp>-<short commit>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,                // Source commit it was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_hash: Option<String>,          // Git blob hash of the profile script it was built with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built_at: Option<u64>,                 // Unix timestamp
+}
+
+/// Global credential settings.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Auth {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_key: Option<String>,               // SSH private key tried after the repository's own key
+}
+
+impl Auth {
+    fn is_empty(&self) -> bool {
+        self.ssh_key.is_none()
     }
 }
 
+/// Everything LUBIG knows about a single registered repository.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Repo {
+    pub path: String,                          // Local source directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,                   // Remote URL it was cloned from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,                // Tracked ref for upgrades
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,                  // Tag mode: glob or semver range to track instead of `branch`
+    #[serde(default = "locked_default")]
+    pub locked: bool,                          // Locked repos are skipped by upgrade
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<String>,                // Commit the repo is locked at (`lubig pin`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,                    // Shallow clone depth kept on upgrade
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub single_branch: bool,                   // Cloned with `--single-branch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_key: Option<String>,               // SSH private key for this remote
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,                   // User-defined labels for selecting repositories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built_commit: Option<String>,          // Source commit of the last successful build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built_profile: Option<String>,         // Profile script hash of the last successful build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,                 // Build output path (holds the generations)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub generations: Vec<Generation>,          // Installed builds, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<String>,                  // Pre-upgrade HEADs, most recent last
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_at: Option<u64>,                 // Unix timestamps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upgraded_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built_at: Option<u64>,
+}
+
+fn locked_default() -> bool {
+    true
+}
+
+fn schema_default() -> u32 {
+    SCHEMA_VERSION
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             directories: Directories::default(),
-            added: BTreeMap::new(),
-            unlocked: BTreeMap::new(),
-            build: BTreeMap::new(),
+            builds: Builds::default(),
+            auth: Auth::default(),
+            repos: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Directories {
+    fn default() -> Self {
+        Self {
+            sources: Some(data_path("sources")),
+            profiles: Some(data_path("profiles")),
+            programs: Some(data_path("programs")),
+        }
+    }
+}
+
+impl Repo {
+    /// Creates a new, locked repository record for the given source path.
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            locked: true,
+            added_at: Some(LocalStuff::timestamp()),
+            ..Self::default()
         }
     }
 }
 
 impl Config {
     /// Loads configuration from `config.toml` if it exists, otherwise returns defaults.
+    /// Older layouts are migrated step by step (after backing up the original file);
+    /// unreadable files and files from a newer LUBIG are reported as errors.
     pub fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
         let path: PathBuf = conf_path();
 
-        if path.exists() {
-            let data = fs::read_to_string(&path)?;
-            let cfg: Config = toml::from_str(&data)?;
-            Ok(cfg)
-        } else {
-            Ok(Config::default())
-        }
-    }
-
-    /// Modifies a section/key in the config and saves it back to disk.
-    pub fn modify_and_save(&mut self, section: &str, key: &str, val: &str) -> std::io::Result<()> {
-        match section {
-            "Directories" => match key {
-                "sources" => self.directories.sources = Some(val.to_string()),
-                "profiles" => self.directories.profiles = Some(val.to_string()),
-                "programs" => self.directories.programs = Some(val.to_string()),
-                _ => eprintln!("Unknown key"),
-            },
-            "Added" => { self.added.insert(key.to_string(), val.to_string()); }
-            "Unlocked" => { self.unlocked.insert(key.to_string(), val.to_string()); }
-            "Build" => { self.build.insert(key.to_string(), val.to_string()); }
-            _ => eprintln!("Unknown section"),
+        if !path.exists() {
+            return Ok(Config::default());
         }
-        save(self)
+
+        let (cfg, version) = Self::parse(&path)?;
+        if version == SCHEMA_VERSION {
+            return Ok(cfg);
+        }
+
+        // Rewrite the file under the config lock, reading it again in case another
+        // lubig process migrated (or changed) it while we were waiting.
+        let _lock = ConfigLock::acquire()?;
+        let (cfg, version) = Self::parse(&path)?;
+
+        if version < SCHEMA_VERSION {
+            // Keep the untouched file before rewriting it in the new layout.
+            let backup = path.with_extension(format!("toml.v{}.bak", version));
+            fs::copy(&path, &backup)?;
+            cfg.save()?;
+            println!("Config migrated from schema version {} to {} (backup: '{}').",
+                version, SCHEMA_VERSION, backup.display());
+        }
+
+        Ok(cfg)
     }
 
-    /// Removes a key from a section and saves the config.
-    pub fn remove_and_save(&mut self, section: &str, key: &str) -> std::io::Result<()> {
-        match section {
-            "Added" => { self.added.remove(key); }
-            "Unlocked" => { self.unlocked.remove(key); }
-            "Build" => { self.build.remove(key); }
-            _ => eprintln!("Unknown section"),
+    /// Reads `config.toml`, migrating older layouts in memory.
+    /// Returns the configuration and the schema version found in the file.
+    fn parse(path: &Path) -> Result<(Config, u32), Box<dyn std::error::Error>> {
+        let data = fs::read_to_string(path)?;
+        let mut table: toml::Table = toml::from_str(&data)
+            .map_err(|e| format!("'{}' is not valid TOML: {}", path.display(), e))?;
+
+        let version = match table.get("schema_version") {
+            None => 1,
+            Some(v) => v.as_integer()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| format!("'{}' has an invalid schema_version", path.display()))?,
+        };
+
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "'{}' uses schema version {}, but this lubig only understands up to {}. Please upgrade lubig.",
+                path.display(), version, SCHEMA_VERSION
+            ).into());
+        }
+
+        if version < SCHEMA_VERSION {
+            for migrate in &MIGRATIONS[(version as usize).saturating_sub(1)..] {
+                migrate(&mut table);
+            }
+            table.insert("schema_version".into(), toml::Value::Integer(SCHEMA_VERSION.into()));
         }
+
+        let cfg: Config = table.try_into()
+            .map_err(|e| format!("'{}' could not be read: {}", path.display(), e))?;
+        Ok((cfg, version))
+    }
+
+    /// Loads the configuration, aborting with an error instead of falling back to defaults.
+    pub fn load() -> Config {
+        match Self::load_config() {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                Text::config_error(&e.to_string());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Saves the current configuration to disk.
+    pub fn save(&self) -> std::io::Result<()> {
         save(self)
     }
-    
-    /// Retrieves a value from a given section/key.
-    pub fn get_value(&self, section: &str, key: &str) -> Option<String> {
-        match section {
-            "Directories" => match key {
-                "sources" => self.directories.sources.clone(),
-                "profiles" => self.directories.profiles.clone(),
-                "programs" => self.directories.programs.clone(),
-                _ => None,
-            },
-            "Added" => self.added.get(key).cloned(),
-            "Unlocked" => self.unlocked.get(key).cloned(),
-            "Build" => self.build.get(key).cloned(),
+
+    /// Returns a configured directory (`sources`, `profiles` or `programs`).
+    pub fn dir(&self, key: &str) -> Option<String> {
+        match key {
+            "sources" => self.directories.sources.clone(),
+            "profiles" => self.directories.profiles.clone(),
+            "programs" => self.directories.programs.clone(),
             _ => None,
         }
     }
 
-    /// Checks if a key exists in a given section.
-    pub fn key_exists(&self, section: &str, key: &str) -> bool {
-        match section {
-            "Directories" => match key {
-                "sources" => self.directories.sources.is_some(),
-                "profiles" => self.directories.profiles.is_some(),
-                "programs" => self.directories.programs.is_some(),
-                _ => false,
-            },
-            "Added" => self.added.contains_key(key),
-            "Unlocked" => self.unlocked.contains_key(key),
-            "Build" => self.build.contains_key(key),
-            _ => false,
+    /// Sets a configured directory.
+    pub fn set_dir(&mut self, key: &str, val: &str) {
+        match key {
+            "sources" => self.directories.sources = Some(val.to_string()),
+            "profiles" => self.directories.profiles = Some(val.to_string()),
+            "programs" => self.directories.programs = Some(val.to_string()),
+            _ => eprintln!("Unknown key"),
+        }
+    }
+
+    /// Returns the build profile path for a repository: the `<profiles>/<name>.sh`
+    /// (or `.bat`) script if it exists, otherwise a declarative `<profiles>/<name>.toml`
+    /// if that exists, otherwise the script path.
+    pub fn profile_path(&self, name: &str) -> PathBuf {
+        let ext = if cfg!(windows) { ".bat" } else { ".sh" };
+        let dir = PathBuf::from(self.dir("profiles").unwrap_or_default());
+        let script = dir.join(format!("{}{}", name, ext));
+        let declarative = dir.join(format!("{}.toml", name));
+
+        if !script.exists() && declarative.exists() { declarative } else { script }
+    }
+
+    /// Returns how to reach a repository's remote: its shallow depth and the SSH keys
+    /// to offer (the repository key first, then the global one).
+    pub fn access(&self, name: &str) -> Access {
+        let repo = self.repo(name);
+        let keys = repo.and_then(|r| r.ssh_key.as_deref())
+            .into_iter()
+            .chain(self.auth.ssh_key.as_deref())
+            .map(PathBuf::from)
+            .collect();
+
+        Access { depth: repo.and_then(|r| r.depth), keys }
+    }
+
+    /// Checks if a repository name is registered.
+    pub fn has_repo(&self, name: &str) -> bool {
+        self.repos.contains_key(name)
+    }
+
+    /// Returns the record of a registered repository.
+    pub fn repo(&self, name: &str) -> Option<&Repo> {
+        self.repos.get(name)
+    }
+
+    /// Returns a mutable record of a registered repository.
+    pub fn repo_mut(&mut self, name: &str) -> Option<&mut Repo> {
+        self.repos.get_mut(name)
+    }
+
+    /// Resolves selectors to registered repository names: `patterns` are names or
+    /// globs, `labels` match repositories carrying that label.
+    /// Fails with the first selector that matches nothing.
+    pub fn select(&self, patterns: &[String], labels: &[String]) -> Result<BTreeSet<String>, String> {
+        let mut selected = BTreeSet::new();
+
+        for pattern in patterns {
+            let found: Vec<&String> = self.repos.keys().filter(|name| LocalStuff::glob_match(pattern, name)).collect();
+            if found.is_empty() {
+                return Err(format!("'{}' is not registered", pattern));
+            }
+            selected.extend(found.into_iter().cloned());
         }
+
+        for label in labels {
+            let found: Vec<&String> = self.repos.iter()
+                .filter(|(_, repo)| repo.labels.contains(label))
+                .map(|(name, _)| name)
+                .collect();
+            if found.is_empty() {
+                return Err(format!("no registered repository is labelled '{}'", label));
+            }
+            selected.extend(found.into_iter().cloned());
+        }
+
+        Ok(selected)
+    }
+
+    /// Registers (or replaces) a repository.
+    pub fn insert_repo(&mut self, name: &str, repo: Repo) {
+        self.repos.insert(name.to_string(), repo);
+    }
+
+    /// Unregisters a repository.
+    pub fn remove_repo(&mut self, name: &str) {
+        self.repos.remove(name);
+    }
+
+    /// Runs a load-modify-save transaction while holding the config lock,
+    /// so concurrent lubig processes can't overwrite each other's changes.
+    pub fn transaction<T, F: FnOnce(&mut Config) -> T>(f: F) -> std::io::Result<T> {
+        let _lock = ConfigLock::acquire()?;
+        let mut cfg = Self::load();
+        let out = f(&mut cfg);
+        cfg.save()?;
+        Ok(out)
     }
 }
 
-/// Returns the path to `config.toml` in the executable's directory.
-pub fn conf_path() -> PathBuf {
-    env::current_exe().unwrap()
-        .parent().unwrap()
-        .join("config.toml")
+/// Exclusive advisory lock on `config.toml.lock`.
+/// The OS releases it when the guard is dropped or the process dies.
+/// It is re-entrant within a thread, so a transaction can load (and migrate) the config.
+pub struct ConfigLock(Option<fs::File>);
+
+thread_local! {
+    /// How many `ConfigLock` guards the current thread holds.
+    static LOCK_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+impl ConfigLock {
+    /// Blocks until no other lubig process holds the config lock.
+    pub fn acquire() -> std::io::Result<Self> {
+        if LOCK_DEPTH.get() > 0 {
+            LOCK_DEPTH.set(LOCK_DEPTH.get() + 1);
+            return Ok(Self(None));
+        }
+
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(conf_path().with_extension("toml.lock"))?;
+
+        if file.try_lock().is_err() {
+            println!("Waiting for another lubig process to release the configuration...");
+            file.lock()?;
+        }
+
+        LOCK_DEPTH.set(1);
+        Ok(Self(Some(file)))
+    }
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        LOCK_DEPTH.set(LOCK_DEPTH.get() - 1);
+        if let Some(file) = &self.0 {
+            file.unlock().ok();
+        }
+    }
+}
+
+/// Resolved location of the LUBIG workspace: the config file and the base
+/// directory for the default `sources`, `profiles` and `programs` paths.
+#[derive(Debug)]
+pub struct Home {
+    pub config: PathBuf,
+    pub data: PathBuf,
+    pub origin: &'static str,                  // Which rule selected this location
+}
+
+static HOME: OnceLock<Home> = OnceLock::new();
+
+/// Resolves the workspace location once per run. Order:
+/// 1. `--config <file>` (defaults live next to the file)
+/// 2. `$LUBIG_HOME`
+/// 3. `config.toml` already next to the executable (portable layout)
+/// 4. XDG: `$XDG_CONFIG_HOME/lubig/config.toml` + `$XDG_DATA_HOME/lubig`
+/// 5. The executable's directory, when no XDG directory can be resolved
+pub fn init_home(cli_config: Option<&str>) -> &'static Home {
+    HOME.get_or_init(|| resolve_home(cli_config))
+}
+
+/// Returns the resolved workspace location.
+pub fn home() -> &'static Home {
+    init_home(None)
+}
+
+fn resolve_home(cli_config: Option<&str>) -> Home {
+    if let Some(file) = cli_config {
+        let config = absolute(PathBuf::from(file));
+        let data = config.parent().map(PathBuf::from).unwrap_or_default();
+        return Home { config, data, origin: "--config" };
+    }
+
+    if let Some(dir) = env::var_os("LUBIG_HOME").filter(|v| !v.is_empty()) {
+        let data = absolute(PathBuf::from(dir));
+        return Home { config: data.join("config.toml"), data, origin: "LUBIG_HOME" };
+    }
+
+    // A config already next to the executable is a portable install: keep using it.
+    let exe_dir = exe_dir();
+    let exe_config = exe_dir.join("config.toml");
+    if exe_config.exists() {
+        return Home { config: exe_config, data: exe_dir, origin: "executable directory" };
+    }
+
+    if let Some((config_dir, data_dir)) = xdg_dirs() {
+        return Home { config: config_dir.join("config.toml"), data: data_dir, origin: "XDG" };
+    }
+
+    Home { config: exe_config, data: exe_dir, origin: "executable directory" }
 }
 
-/// Returns a subdirectory path relative to the executable's directory.
-pub fn exe_path(sub: &str) -> String {
-    let base_dir: PathBuf = env::current_exe()
+/// Returns `$XDG_CONFIG_HOME/lubig` and `$XDG_DATA_HOME/lubig`, falling back to
+/// `~/.config/lubig` and `~/.local/share/lubig` as the XDG spec requires.
+fn xdg_dirs() -> Option<(PathBuf, PathBuf)> {
+    let xdg = |var: &str, fallback: &str| -> Option<PathBuf> {
+        env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| env::var_os("HOME").filter(|h| !h.is_empty()).map(|h| PathBuf::from(h).join(fallback)))
+    };
+
+    Some((xdg("XDG_CONFIG_HOME", ".config")?.join("lubig"), xdg("XDG_DATA_HOME", ".local/share")?.join("lubig")))
+}
+
+fn exe_dir() -> PathBuf {
+    env::current_exe()
         .unwrap()
         .parent()
         .unwrap()
-        .to_path_buf();
+        .to_path_buf()
+}
+
+fn absolute(path: PathBuf) -> PathBuf {
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// Returns the path to the resolved `config.toml`.
+pub fn conf_path() -> PathBuf {
+    home().config.clone()
+}
 
-    base_dir.join(sub).to_string_lossy().into_owned()
+/// Returns the upgrade history file of a repository (`history/<name>.log` in the data directory).
+pub fn history_path(name: &str) -> PathBuf {
+    home().data.join("history").join(format!("{}.log", name))
+}
+
+/// Returns the build log directory of a repository (`logs/<name>` in the data directory).
+pub fn log_dir(name: &str) -> PathBuf {
+    home().data.join("logs").join(name)
+}
+
+/// Returns a subdirectory path relative to the workspace data directory.
+pub fn data_path(sub: &str) -> String {
+    home().data.join(sub).to_string_lossy().into_owned()
 }
 
 /// Generates a default config file.
 pub fn generate_config() -> std::io::Result<()> {
+    if let Some(parent) = conf_path().parent() {
+        fs::create_dir_all(parent)?;
+    }
     save(&Config::default())
 }
 
 /// Saves the given config to `config.toml` in pretty TOML format.
+/// The write is atomic, so a crash never leaves a truncated config behind.
 pub fn save(cfg: &Config) -> std::io::Result<()> {
-    fs::write(conf_path(), toml::to_string_pretty(cfg).unwrap())
-}
\ No newline at end of file
+    let data = toml::to_string_pretty(cfg).map_err(std::io::Error::other)?;
+    LocalStuff::write_atomic(conf_path(), &data)
+}
+
+/// v1 -> v2: folds the `[added]`, `[unlocked]` and `[build]` tables into `[repos.<name>]` records.
+fn migrate_v1_to_v2(table: &mut toml::Table) {
+    let mut take = |lower: &str, upper: &str| -> toml::Table {
+        let mut out = toml::Table::new();
+        for key in [upper, lower] {
+            if let Some(toml::Value::Table(t)) = table.remove(key) {
+                out.extend(t);
+            }
+        }
+        out
+    };
+
+    let dirs = take("directories", "Directories");
+    let added = take("added", "Added");
+    let unlocked = take("unlocked", "Unlocked");
+    let build = take("build", "Build");
+
+    if !dirs.is_empty() {
+        table.insert("directories".into(), toml::Value::Table(dirs));
+    }
+
+    let mut repos = toml::Table::new();
+    for (name, path) in added {
+        let mut repo = toml::Table::new();
+        repo.insert("path".into(), path);
+
+        match unlocked.get(&name) {
+            Some(branch) => {
+                repo.insert("branch".into(), branch.clone());
+                repo.insert("locked".into(), toml::Value::Boolean(false));
+            }
+            None => { repo.insert("locked".into(), toml::Value::Boolean(true)); }
+        }
+
+        if let Some(out) = build.get(&name) {
+            repo.insert("build".into(), out.clone());
+        }
+
+        repos.insert(name, toml::Value::Table(repo));
+    }
+    table.insert("repos".into(), toml::Value::Table(repos));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_v1_tables_into_repo_records() {
+        let mut table: toml::Table = toml::from_str(r#"
+            [Directories]
+            sources = "/w/sources"
+
+            [added]
+            locked = "/w/sources/locked"
+            tracked = "/w/sources/tracked"
+
+            [unlocked]
+            tracked = "main"
+
+            [Build]
+            locked = "/w/programs/locked"
+        "#).unwrap();
+
+        migrate_v1_to_v2(&mut table);
+        table.insert("schema_version".into(), toml::Value::Integer(2));
+        let cfg: Config = table.try_into().unwrap();
+
+        assert_eq!(cfg.directories.sources.as_deref(), Some("/w/sources"));
+        assert_eq!(cfg.repos.len(), 2);
+
+        let locked = cfg.repo("locked").unwrap();
+        assert_eq!(locked.path, "/w/sources/locked");
+        assert!(locked.locked);
+        assert_eq!(locked.branch, None);
+        assert_eq!(locked.build.as_deref(), Some("/w/programs/locked"));
+
+        let tracked = cfg.repo("tracked").unwrap();
+        assert!(!tracked.locked);
+        assert_eq!(tracked.branch.as_deref(), Some("main"));
+        assert_eq!(tracked.build, None);
+    }
+
+    #[test]
+    fn migrates_an_empty_v1_config() {
+        let mut table = toml::Table::new();
+        migrate_v1_to_v2(&mut table);
+
+        assert_eq!(table.get("repos"), Some(&toml::Value::Table(toml::Table::new())));
+        assert!(!table.contains_key("directories"));
+    }
+
+    fn workspace() -> Config {
+        let mut cfg = Config::default();
+        for (name, labels) in [("nvim", vec!["editor"]), ("nvim-lsp", vec!["editor", "lsp"]), ("ripgrep", vec!["cli"])] {
+            let labels = labels.into_iter().map(String::from).collect();
+            cfg.insert_repo(name, Repo { labels, ..Repo::default() });
+        }
+        cfg
+    }
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn selects_by_glob_and_label() {
+        let cfg = workspace();
+        let selected = |patterns: &[&str], labels: &[&str]| {
+            cfg.select(&names(patterns), &names(labels)).map(|s| s.into_iter().collect::<Vec<_>>())
+        };
+
+        assert_eq!(selected(&["nvim*"], &[]), Ok(names(&["nvim", "nvim-lsp"])));
+        assert_eq!(selected(&["ripgrep"], &["lsp"]), Ok(names(&["nvim-lsp", "ripgrep"])));
+        assert_eq!(selected(&[], &["editor"]), Ok(names(&["nvim", "nvim-lsp"])));
+        assert_eq!(selected(&["nvim"], &["editor"]), Ok(names(&["nvim", "nvim-lsp"])));
+        assert_eq!(selected(&[], &[]), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn select_rejects_unmatched_selectors() {
+        let cfg = workspace();
+
+        assert_eq!(cfg.select(&names(&["emacs*"]), &[]), Err("'emacs*' is not registered".to_string()));
+        assert!(cfg.select(&names(&["nvim"]), &names(&["games"])).is_err());
+    }
+}
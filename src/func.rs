@@ -1,10 +1,18 @@
 use std::{
-    process::{Command, Stdio},
+    collections::{BTreeMap, BTreeSet},
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
     path::{Path, PathBuf}
 };
 
-use crate::conf::Config;
-use crate::util::{LocalStuff, RemoteStuff};
+use crate::buildlog;
+use crate::conf::{self, Config, Generation, Repo, HISTORY_LIMIT};
+use crate::lockfile::LockFile;
+use crate::manifest::{Manifest, ManifestRepo};
+use crate::profile::Profile;
+use crate::template;
+use crate::text::Text;
+use crate::util::{Access, CloneOptions, LocalStuff, PullOutcome, RemoteStuff, UpgradeStatus};
 
 /// Core executor for LUBIG operations.
 /// Each method corresponds to a high-level command.
@@ -13,11 +21,17 @@ pub struct Execute;
 impl Execute {
 
     /// Clone a remote Git repository into the sources directory and register it.
-    pub fn get(url: &String, name: &String) -> std::io::Result<()> {
-        let cmd = Config::load_config().unwrap_or_default();
+    pub fn get(url: &str, name: &str, opts: &CloneOptions) -> std::io::Result<()> {
+        Self::clone_repo(url, name, opts, true)
+    }
+
+    /// Clones and registers a repository, suggesting a profile template
+    /// afterwards when `suggest` is set and it has no profile yet.
+    fn clone_repo(url: &str, name: &str, opts: &CloneOptions, suggest: bool) -> std::io::Result<()> {
+        let cmd = Config::load();
 
         // Retrieve the configured sources directory.
-        let path_str = match cmd.get_value("Directories", "sources") {
+        let path_str = match cmd.dir("sources") {
             Some(p) => p,
             None => {
                 eprintln!("Error: Unknown Configuration");
@@ -29,21 +43,19 @@ impl Execute {
         let final_str = format!("{}/{}", path_str, name);
         let path = Path::new(&final_str);
 
-        // Execute `git clone` command.
-        let status = Command::new("git")
-            .arg("clone")
-            .arg(url)
-            .arg(path)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .status()?;
-
-        // If clone succeeds, register the repository.
-        if status.success() {
-            Self::add(&path, name);
-            println!("SUCCESS: getting '{}'", name);
-        } else {
-            eprintln!("ERROR: Failed to clone '{}'", name);
+        // Clone natively through git2, offering the repository key before the global one.
+        let access = Access {
+            depth: opts.depth,
+            keys: opts.key.iter().chain(&cmd.auth.ssh_key).map(PathBuf::from).collect(),
+        };
+        match RemoteStuff::clone(url, path, opts, &access) {
+            Ok(_) => {
+                // Register the repository.
+                Self::register(&path, name, Some(url), opts, suggest);
+                LockFile::record(name).ok();
+                println!("SUCCESS: getting '{}'", name);
+            }
+            Err(e) => println!("ERROR: Failed to clone '{}': {}", name, e.message()),
         }
 
         Ok(())
@@ -51,9 +63,15 @@ impl Execute {
 
     /// Register a repository in the configuration.
     /// Moves it into the sources directory if needed.
-    pub fn add<P: AsRef<Path>>(path: &P, name: &str) {
-        let mut config = Config::load_config().unwrap_or_default();
-        let src_path_str = config.get_value("Directories", "sources").unwrap() + "/" + name;
+    pub fn add<P: AsRef<Path>>(path: &P, name: &str, url: Option<&str>, opts: &CloneOptions) {
+        Self::register(path, name, url, opts, true);
+    }
+
+    /// Registers a repository, suggesting a profile template afterwards
+    /// when `suggest` is set and it has no profile yet.
+    fn register<P: AsRef<Path>>(path: &P, name: &str, url: Option<&str>, opts: &CloneOptions, suggest: bool) {
+        let config = Config::load();
+        let src_path_str = config.dir("sources").unwrap() + "/" + name;
         let src_path = Path::new(&src_path_str);
 
         // Ensure the target directory exists.
@@ -63,111 +81,737 @@ impl Execute {
 
         // Move the repository into the sources directory if it's not already there.
         if !LocalStuff::is_subdir(src_path, path) {
-            LocalStuff::move_dir(&path, src_path).ok();
+            LocalStuff::move_dir(path, src_path).ok();
         }
 
         // Save the registration in the config.
-        config.modify_and_save("Added", name, &src_path_str).ok();
-        println!("'{}' Added", name)
-    }
-
-    /// Upgrade all unlocked repositories.
-    /// If a repository is marked for build, rebuild it after upgrade.
-    pub fn upgrade() {
-        let config = Config::load_config().unwrap_or_default();
-
-        for key in config.added.keys() {
-            if config.key_exists("Unlocked", key) {
-                let path = config.get_value("Added", key).unwrap();
-                let branch = config.get_value("Unlocked", key).unwrap();
-
-                // Pull latest changes from the remote branch.
-                RemoteStuff::pull_fast_forward(&path, &branch).ok();
-                
-                // If build flag exists, rebuild after upgrade.
-                if config.key_exists("Build", key) {
-                    Self::build(key).ok();
+        let repo = Repo {
+            url: url.map(str::to_string),
+            branch: opts.branch.clone(),
+            depth: opts.depth,
+            single_branch: opts.single_branch,
+            ssh_key: opts.key.clone(),
+            ..Repo::new(&src_path_str)
+        };
+        match Config::transaction(|config| config.insert_repo(name, repo)) {
+            Ok(()) => println!("'{}' Added", name),
+            Err(e) => {
+                Text::save_error(&e.to_string());
+                return;
+            }
+        }
+
+        // Offer a profile matching the detected build system if there is none yet.
+        if suggest && !Config::load().profile_path(name).exists() {
+            Text::detected_build(name, template::detect(&src_path_str));
+        }
+    }
+
+    /// Upgrade the unlocked repositories in `only` (every repository when `None`),
+    /// fetching up to `jobs` of them at once.
+    /// Each repository's report is printed in registration order once every fetch is done,
+    /// then repositories with a build are rebuilt one at a time. Ends with a summary table.
+    /// A failed repository doesn't stop the others unless `fail_fast` is set, in which case
+    /// no new fetch or build is started after the first failure. Rebuilds are skipped when
+    /// nothing changed since the last build, unless `force` is set.
+    /// Returns `false` when any fetch or rebuild failed.
+    pub fn upgrade(only: Option<&BTreeSet<String>>, jobs: usize, fail_fast: bool, force: bool) -> bool {
+        let config = Config::load();
+        let selected: Vec<(&String, &Repo)> = config.repos.iter()
+            .filter(|(key, _)| only.is_none_or(|only| only.contains(*key)))
+            .collect();
+        let unlocked: Vec<(&String, &Repo)> = selected.iter().copied().filter(|(_, repo)| !repo.locked).collect();
+
+        // Fetch and fast-forward concurrently; each worker touches only its own repository.
+        // Single-line progress is only drawn when fetching one repository at a time.
+        Text::set_progress(jobs <= 1);
+        let failed = AtomicBool::new(false);
+        let pulled = LocalStuff::parallel(&unlocked, jobs, |(key, repo)| {
+            if fail_fast && failed.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            let branch = repo.tags.as_deref().or(repo.branch.as_deref()).unwrap_or("main");
+            let access = config.access(key);
+            let before = RemoteStuff::head_commit(&repo.path).ok();
+
+            // Pull latest changes from the remote branch, or move to the newest matching tag.
+            let result = match &repo.tags {
+                Some(pattern) => RemoteStuff::upgrade_to_tag(&repo.path, pattern, &access),
+                None => RemoteStuff::pull_fast_forward(&repo.path, branch, &access),
+            };
+            if result.as_ref().map_or(true, PullOutcome::is_refused) {
+                failed.store(true, Ordering::Relaxed);
+            }
+            Some((before, result))
+        });
+        Text::set_progress(true);
+
+        let mut pulled: BTreeMap<&String, _> = unlocked.iter().map(|(key, _)| *key).zip(pulled).collect();
+        let mut summary = Vec::new();
+        let mut rebuild = Vec::new();
+
+        for (key, repo) in selected {
+            let (before, result) = match pulled.remove(key) {
+                Some(Some(pulled)) => pulled,
+                Some(None) => {
+                    summary.push((key.clone(), UpgradeStatus::Skipped("not attempted (--fail-fast)".into())));
+                    continue;
+                }
+                None => {
+                    Self::verify_pin(key, repo);
+                    summary.push((key.clone(), UpgradeStatus::Skipped("locked".into())));
+                    continue;
+                }
+            };
+
+            let branch = repo.tags.as_deref().or(repo.branch.as_deref()).unwrap_or("main");
+            let outcome = match result {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    println!("ERROR: Failed to upgrade '{}': {}", key, e.message());
+                    summary.push((key.clone(), UpgradeStatus::FetchFailed(e.message().to_string())));
+                    continue;
+                }
+            };
+
+            Text::pull_outcome(key, branch, &outcome);
+            let status = match &outcome {
+                PullOutcome::UpToDate => UpgradeStatus::UpToDate,
+                PullOutcome::FastForwarded { from, to } => UpgradeStatus::Upgraded(format!("{:.7}..{:.7}", from.to_string(), to.to_string())),
+                PullOutcome::Created { to } => UpgradeStatus::Upgraded(format!("created at {:.7}", to.to_string())),
+                PullOutcome::Tagged { tag, .. } => UpgradeStatus::Upgraded(format!("tag '{}'", tag)),
+                PullOutcome::Diverged { .. } => UpgradeStatus::Diverged,
+                PullOutcome::Dirty(files) => UpgradeStatus::Refused(format!("{} modified file(s)", files.len())),
+                PullOutcome::NoMatchingTag => UpgradeStatus::Refused(format!("no tag matches '{}'", branch)),
+            };
+            if outcome.is_refused() {
+                summary.push((key.clone(), status));
+                continue;
+            }
+
+            // Remember the pre-upgrade HEAD so the upgrade can be rolled back.
+            let after = RemoteStuff::head_commit(&repo.path).ok();
+
+            if let (Some(from), Some(to)) = (&before, &after) && from != to {
+                Self::changelog(key, branch, &repo.path, from, to);
+            }
+            Config::transaction(|config| {
+                if let Some(repo) = config.repo_mut(key) {
+                    repo.upgraded_at = Some(LocalStuff::timestamp());
+
+                    if let Some(before) = before.filter(|b| after.as_ref() != Some(b)) {
+                        repo.history.push(before);
+                        let excess = repo.history.len().saturating_sub(HISTORY_LIMIT);
+                        repo.history.drain(..excess);
+                    }
                 }
+            }).ok();
+            LockFile::record(key).ok();
+
+            // If a build output exists, rebuild after every fetch has been reported.
+            if repo.build.is_some() {
+                rebuild.push(summary.len());
             }
+            summary.push((key.clone(), status));
         }
+
+        // Builds run one at a time, in registration order.
+        for i in rebuild {
+            let key = summary[i].0.clone();
+            if fail_fast && summary.iter().any(|(_, status)| status.is_failure()) {
+                println!("SKIPPED: '{}' was not rebuilt (--fail-fast)", key);
+                continue;
+            }
+
+            if let Err(e) = Self::build(&key, force) {
+                println!("ERROR: Failed to rebuild '{}': {}", key, e);
+                summary[i].1 = UpgradeStatus::BuildFailed(e.to_string());
+            }
+        }
+
+        Text::upgrade_summary(&summary);
+        !summary.iter().any(|(_, status)| status.is_failure())
     }
 
-    /// Build a registered repository using its profile script.
-    pub fn build(name: &str) -> std::io::Result<()> {
-        let mut config = Config::load_config().unwrap_or_default();
-        
-        // Determine script extension based on OS.
-        let ext = if cfg!(windows) { ".bat" } else { ".sh" };
+    /// Print the commits an upgrade applied and append them to the repository's history file.
+    fn changelog(name: &str, branch: &str, path: &str, from: &str, to: &str) {
+        let entries = match RemoteStuff::changelog(path, from, to) {
+            Ok(entries) if !entries.is_empty() => entries,
+            Ok(_) => return,
+            Err(e) => {
+                println!("WARNING: '{}' changelog unavailable: {}", name, e.message());
+                return;
+            }
+        };
 
-        // Retrieve configured directories.
-        let src_path_str = config.get_value("Directories", "sources").unwrap();
-        let prof_path_str = config.get_value("Directories", "profiles").unwrap();
-        let prog_path_str = config.get_value("Directories", "programs").unwrap();
+        let body = Text::format_changelog(&entries);
+        print!("{}", body);
+
+        let header = format!("[{}] '{}' {} {}..{} ({} commit(s))\n",
+            LocalStuff::format_timestamp(LocalStuff::timestamp()), name, branch, &from[..7], &to[..7], entries.len());
+        LocalStuff::append(conf::history_path(name), &(header + &body)).ok();
+    }
+
+    /// Fetch every unlocked repository and report the upstream commits that
+    /// `upgrade` would apply, without changing anything. Optionally as JSON.
+    pub fn outdated(json: bool) {
+        let config = Config::load();
+        let mut report = BTreeMap::new();
+
+        for (key, repo) in &config.repos {
+            if repo.locked {
+                Self::verify_pin(key, repo);
+                continue;
+            }
+
+            let branch = repo.tags.as_deref().or(repo.branch.as_deref()).unwrap_or("main");
+
+            let access = config.access(key);
+            let result = match &repo.tags {
+                Some(pattern) => RemoteStuff::pending_tag(&repo.path, pattern, &access)
+                    .and_then(|p| p.ok_or_else(|| git2::Error::from_str(&format!("no tag matches '{}'", pattern)))),
+                None => RemoteStuff::pending(&repo.path, branch, &access),
+            };
 
-        let mut src_path = PathBuf::from(src_path_str);
-        let mut prof_path = PathBuf::from(prof_path_str);
-        let mut prog_path = PathBuf::from(prog_path_str);
+            match result {
+                Ok(pending) => {
+                    if !json {
+                        Text::pending(key, branch, &pending);
+                    }
+                    report.insert(key.clone(), Ok(pending));
+                }
+                Err(e) => {
+                    if !json {
+                        println!("ERROR: Failed to fetch '{}': {}", key, e.message());
+                    }
+                    report.insert(key.clone(), Err(e.message().to_string()));
+                }
+            }
+        }
+
+        if json {
+            let report: BTreeMap<_, _> = report.into_iter()
+                .map(|(name, res)| (name, match res {
+                    Ok(pending) => serde_json::to_value(pending).unwrap_or_default(),
+                    Err(error) => serde_json::json!({ "error": error }),
+                }))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+        }
+    }
+
+    /// Check out a commit, tag or branch (detached) and lock the repository at it.
+    pub fn pin(name: &str, reference: &str) {
+        let config = Config::load();
+        let repo = config.repo(name).unwrap().clone();
+        let access = config.access(name);
+
+        let oid = match RemoteStuff::resolve_ref(&repo.path, reference, &access) {
+            Ok(oid) => oid.to_string(),
+            Err(e) => {
+                println!("ERROR: '{}' could not resolve '{}': {}", name, reference, e.message());
+                return;
+            }
+        };
+
+        if let Err(e) = RemoteStuff::checkout_commit(&repo.path, &oid, &access) {
+            println!("ERROR: '{}' could not be checked out at {}: {}", name, oid, e.message());
+            return;
+        }
+
+        let saved = Config::transaction(|config| {
+            if let Some(repo) = config.repo_mut(name) {
+                repo.pinned = Some(oid.clone());
+                repo.locked = true;
+            }
+        });
+
+        match saved {
+            Ok(()) => {
+                LockFile::record(name).ok();
+                println!("SUCCESS: '{}' pinned at '{}' ({}) and locked for updates.", name, reference, oid);
+            }
+            Err(e) => Text::save_error(&e.to_string()),
+        }
+    }
+
+    /// Warn when a pinned repository's worktree no longer matches its pin.
+    fn verify_pin(name: &str, repo: &Repo) {
+        let Some(pinned) = &repo.pinned else { return; };
+
+        match RemoteStuff::head_commit(&repo.path) {
+            Ok(head) if &head != pinned => {
+                println!("WARNING: '{}' is pinned at {} but its worktree is at {}. Was it moved manually?", name, pinned, head);
+            }
+            Ok(_) => {}
+            Err(e) => println!("WARNING: '{}' pin could not be verified: {}", name, e.message()),
+        }
+
+        if let Ok(files) = RemoteStuff::dirty_files_at(&repo.path) && !files.is_empty() {
+            println!("WARNING: '{}' is pinned at {} but has {} locally modified file(s).", name, pinned, files.len());
+        }
+    }
+
+    /// Restore the commit a repository had `steps` upgrades ago, rebuild it if it
+    /// has a build, and lock it so the next upgrade doesn't reapply the bad commit.
+    pub fn rollback(name: &str, steps: usize) {
+        let config = Config::load();
+        let repo = config.repo(name).unwrap().clone();
+
+        if steps == 0 || steps > repo.history.len() {
+            println!("ERROR: '{}' has {} recorded upgrade(s); cannot roll back {} step(s).", name, repo.history.len(), steps);
+            return;
+        }
 
-        // Append repository name to source path.
-        src_path = src_path.join(name);
-        // Append script filename to profile path.
-        prof_path = prof_path.join(format!("{}{}", name, ext));
+        let target = &repo.history[repo.history.len() - steps];
 
-        // Remove existing build output if present.
-        if prog_path.join(name).exists() {
-            LocalStuff::delete_dir(prog_path.join(name)).ok();
+        if let Err(e) = RemoteStuff::checkout_commit(&repo.path, target, &config.access(name)) {
+            println!("ERROR: '{}' could not be rolled back to {}: {}", name, target, e.message());
+            return;
         }
 
-        // Execute the build script, passing the programs directory as argument.
-        let status = Command::new(&prof_path)
-            .arg(prog_path.to_str().unwrap())
-            .current_dir(&src_path)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .status()?;
+        Config::transaction(|config| {
+            if let Some(repo) = config.repo_mut(name) {
+                let keep = repo.history.len() - steps;
+                repo.history.truncate(keep);
+                repo.locked = true;
+            }
+        }).ok();
+        LockFile::record(name).ok();
+        println!("SUCCESS: '{}' rolled back to {} and locked for updates.", name, target);
+
+        if repo.build.is_some() {
+            Self::build(name, false).ok();
+        }
+    }
+
+    /// Build a registered repository using its profile script.
+    /// Skipped when the source commit and the profile script are the same as in the
+    /// last successful build and its output is still installed, unless `force` is set.
+    pub fn build(name: &str, force: bool) -> std::io::Result<()> {
+        let config = Config::load();
+        
+        // Retrieve configured directories.
+        let prog_path_str = config.dir("programs").unwrap();
+
+        // Build from the exact path recorded for the repository.
+        let repo = config.repo(name).unwrap();
+        Self::verify_pin(name, repo);
+        let src_path = PathBuf::from(&repo.path);
+        let prof_path = config.profile_path(name);
+        let prog_path = PathBuf::from(prog_path_str);
+        let commit = RemoteStuff::head_commit(&src_path).ok();
+        let profile_hash = LocalStuff::file_hash(&prof_path);
 
-        if !status.success() {
+        if !prof_path.exists() {
+            let hint = match template::detect(&src_path) {
+                Some((kind, file)) => format!(" Found {}: create one with 'lubig profile init {} --template {}'.", file, name, kind),
+                None => format!(" Create one with 'lubig profile init {}'.", name),
+            };
             return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "ERROR: Script failed.",
+                std::io::ErrorKind::NotFound,
+                format!("no profile found at '{}' (or '{}').{}", prof_path.display(), prof_path.with_extension("toml").display(), hint),
             ));
         }
 
-        // Mark the repository as built in the config.
-        prog_path = prog_path.join(name);
-        config.modify_and_save("Build", name, prog_path.to_str().unwrap()).ok();
-        println!("SUCCESS: Build complete: {}", prog_path.to_str().unwrap());
+        let installed = repo.build.as_ref().is_some_and(|path| Path::new(path).exists());
+        if !force && installed && commit.is_some() && commit == repo.built_commit && profile_hash == repo.built_profile {
+            println!("'{}' build is up to date (commit {:.7}, profile unchanged). Use --force to rebuild.", name, commit.unwrap_or_default());
+            return Ok(());
+        }
+
+        // Run the script against a staging directory inside the programs directory,
+        // so the previous output stays untouched until the new one has been built.
+        let staging = prog_path.join(format!(".{}.staging.{}", name, std::process::id()));
+        LocalStuff::delete_dir(&staging).ok();
+        LocalStuff::generate_path(&staging)?;
+
+        // Execute the build script, passing the staging directory as argument, or the
+        // steps of a declarative profile, and keep the output in logs/<name>/<timestamp>.log.
+        let steps = if prof_path.extension().is_some_and(|ext| ext == "toml") {
+            let profile = Profile::load(&prof_path).map_err(std::io::Error::other)
+                .inspect_err(|_| { LocalStuff::delete_dir(&staging).ok(); })?;
+            profile.commands(&src_path, &staging)
+        } else {
+            let mut command = Command::new(&prof_path);
+            command.arg(&staging).current_dir(&src_path);
+            vec![command]
+        };
+        let header = buildlog::Header {
+            name: name.to_string(),
+            commit: commit.clone(),
+            profile_hash: profile_hash.clone(),
+        };
+        let (outcome, log) = buildlog::run(steps, &header)
+            .inspect_err(|_| { LocalStuff::delete_dir(&staging).ok(); })?;
+
+        let output = staging.join(name);
+        let root = prog_path.join(name);
+        let generation = Generation {
+            id: Self::generation_id(&root, commit.as_deref()),
+            commit: commit.clone(),
+            profile_hash: profile_hash.clone(),
+            built_at: Some(LocalStuff::timestamp()),
+        };
+
+        // Install the fresh output as a new generation and point `current` at it.
+        // Each failure carries the reason recorded in the log and the one reported here.
+        let installed = match outcome {
+            Err(reason) => Err((reason.clone(), format!("build profile '{}' failed ({})", prof_path.display(), reason))),
+            // A script that exits cleanly without producing `<name>` installed nothing:
+            // leave the build record alone so the next build doesn't consider it up to date.
+            Ok(()) if output.symlink_metadata().is_err() => Err((
+                format!("no '{}' produced", name),
+                format!("build profile '{}' produced no '{}' in the directory it was given", prof_path.display(), name),
+            )),
+            Ok(()) => Self::adopt_legacy_build(name, &root, repo)
+                .and_then(|_| LocalStuff::generate_path(&root))
+                .and_then(|_| std::fs::rename(&output, root.join(&generation.id)))
+                .and_then(|_| LocalStuff::point_symlink(root.join("current"), &generation.id))
+                .map_err(|e| (format!("install failed: {}", e), format!("the build of '{}' could not be installed: {}", name, e))),
+        };
+        LocalStuff::delete_dir(&staging).ok();
+
+        // Only now is the result known: complete the log header with it.
+        let log_path = log.path().to_path_buf();
+        if let Err(e) = log.finish(&installed.as_ref().map(|_| ()).map_err(|(reason, _)| reason.clone())) {
+            println!("WARNING: Build log '{}' could not be written: {}", log_path.display(), e);
+        }
+        buildlog::prune(name, config.builds.logs.max(1));
+
+        if let Err((_, message)) = installed {
+            return Err(std::io::Error::other(format!("{}; the previous build was kept (log: {})", message, log_path.display())));
+        }
+
+        // Mark the repository as built in the config, pruning generations beyond the retention.
+        let keep = config.builds.keep.max(1);
+        let pruned = Config::transaction(|config| {
+            let Some(repo) = config.repo_mut(name) else { return Vec::new(); };
+            repo.build = Some(root.to_string_lossy().into_owned());
+            repo.built_commit = commit;
+            repo.built_profile = profile_hash;
+            repo.built_at = Some(LocalStuff::timestamp());
+
+            repo.generations.push(generation);
+            let excess = repo.generations.len().saturating_sub(keep);
+            repo.generations.drain(..excess).collect::<Vec<_>>()
+        })?;
+
+        for old in pruned {
+            LocalStuff::delete_any(root.join(&old.id)).ok();
+        }
+        LockFile::record(name).ok();
+        println!("SUCCESS: Build complete: {}", root.join("current").display());
 
         Ok(())
     }
 
+    /// Returns an unused generation id, `<timestamp>-<short commit>`, suffixed with
+    /// `-2`, `-3`... when another build of the same second already took it.
+    fn generation_id(root: &Path, commit: Option<&str>) -> String {
+        let base = format!("{}-{:.7}", LocalStuff::timestamp(), commit.unwrap_or("unknown"));
+
+        std::iter::once(base.clone())
+            .chain((2..).map(|n| format!("{}-{}", base, n)))
+            .find(|id| root.join(id).symlink_metadata().is_err())
+            .unwrap_or(base)
+    }
+
+    /// Move a build made before generations existed (`programs/<name>` holding the
+    /// output itself) into `programs/<name>/legacy`, so it stays available to `switch`.
+    fn adopt_legacy_build(name: &str, root: &Path, repo: &Repo) -> std::io::Result<()> {
+        if root.symlink_metadata().is_err() || root.join("current").symlink_metadata().is_ok() {
+            return Ok(());
+        }
+
+        let aside = root.with_file_name(format!(".{}.legacy.{}", name, std::process::id()));
+        std::fs::rename(root, &aside)?;
+        LocalStuff::generate_path(root)?;
+        std::fs::rename(&aside, root.join("legacy"))?;
+        LocalStuff::point_symlink(root.join("current"), "legacy")?;
+
+        let legacy = Generation {
+            id: "legacy".into(),
+            commit: repo.built_commit.clone(),
+            profile_hash: repo.built_profile.clone(),
+            built_at: repo.built_at,
+        };
+        Config::transaction(|config| {
+            if let Some(repo) = config.repo_mut(name) {
+                repo.generations.insert(0, legacy);
+            }
+        })
+    }
+
+    /// Show the build logs of a repository: the latest one, the latest failed one, or a list.
+    pub fn logs(name: &str, mode: &str) {
+        let entries = buildlog::list(name);
+
+        let entry = match mode {
+            "--list" => {
+                Text::build_logs(name, &entries);
+                return;
+            }
+            "--failed" => entries.iter().rev().find(|e| e.failed()),
+            _ => entries.last(),
+        };
+
+        let Some(entry) = entry else {
+            println!("'{}' has no {}build logs.", name, if mode == "--failed" { "failed " } else { "" });
+            return;
+        };
+
+        match std::fs::read_to_string(&entry.path) {
+            Ok(contents) => print!("{}", contents),
+            Err(e) => println!("ERROR: Unable to read '{}': {}", entry.path.display(), e),
+        }
+    }
+
+    /// Write a ready-to-edit profile script for a repository from a built-in template.
+    /// Without `template`, the build system of a registered repository is detected,
+    /// falling back to a skeleton. Never overwrites an existing profile.
+    pub fn profile_init(name: &str, template: Option<&str>) {
+        let config = Config::load();
+        let path = config.profile_path(name);
+
+        if path.exists() {
+            println!("ERROR: '{}' already has a profile: '{}'", name, path.display());
+            return;
+        }
+
+        let detected = match template {
+            Some(_) => None,
+            None => config.repo(name).and_then(|repo| template::detect(&repo.path)),
+        };
+        if let Some((kind, file)) = detected {
+            println!("Detected {} build system ({}).", kind, file);
+        }
+        let template = template.or(detected.map(|(kind, _)| kind));
+
+        let Some(script) = template::render(template, name) else {
+            println!("ERROR: Unknown template '{}'. Available: {}", template.unwrap_or_default(), template::names().join(", "));
+            return;
+        };
+
+        match LocalStuff::write_script(&path, &script) {
+            Ok(()) => println!("SUCCESS: '{}' profile written to '{}'. Review it before running 'lubig build {}'.", name, path.display(), name),
+            Err(e) => println!("ERROR: Unable to write '{}': {}", path.display(), e),
+        }
+    }
+
+    /// List the installed build generations of a repository.
+    pub fn generations(name: &str) {
+        let config = Config::load();
+        let repo = config.repo(name).unwrap();
+        let current = repo.build.as_ref()
+            .and_then(|root| LocalStuff::symlink_target(Path::new(root).join("current")));
+
+        Text::generations(name, &repo.generations, current.as_deref());
+    }
+
+    /// Point a repository's `current` build at another installed generation.
+    pub fn switch(name: &str, id: &str) {
+        let config = Config::load();
+        let repo = config.repo(name).unwrap();
+
+        let (Some(root), Some(generation)) = (&repo.build, repo.generations.iter().find(|g| g.id == id)) else {
+            println!("ERROR: '{}' has no build generation '{}'. Use 'lubig generations {}' to list them.", name, id, name);
+            return;
+        };
+
+        if let Err(e) = LocalStuff::point_symlink(Path::new(root).join("current"), id) {
+            println!("ERROR: '{}' could not be switched to '{}': {}", name, id, e);
+            return;
+        }
+
+        Config::transaction(|config| {
+            if let Some(repo) = config.repo_mut(name) {
+                repo.built_commit = generation.commit.clone();
+                repo.built_profile = generation.profile_hash.clone();
+            }
+        }).ok();
+        LockFile::record(name).ok();
+        println!("SUCCESS: '{}' now runs generation '{}'", name, id);
+    }
+
     /// Remove a registered repository and its associated build artifacts.
     pub fn remove(name: &str) {
-        let mut config = Config::load_config().unwrap_or_default();
-        let src_path_str = config.get_value("Added", name).unwrap().to_string();
-        let src_path = Path::new(&src_path_str);
+        let config = Config::load();
+        let repo = config.repo(name).unwrap().clone();
+        let src_path = Path::new(&repo.path);
 
         // Remove build artifacts if they exist.
-        if config.key_exists("Build", name) {
-            let path_str = config.get_value("Build", name).unwrap().to_string();
-            let path = Path::new(&path_str);
-            
+        if let Some(path_str) = &repo.build {
+            let path = Path::new(path_str);
+
             if path.exists() {
                 LocalStuff::delete_dir(path).ok();
             }
-            config.remove_and_save("Build", name).ok();
         }
 
-        // Remove from unlocked list if present.
-        if config.key_exists("Unlocked", name) {
-            config.remove_and_save("Unlocked", name).ok();
-        }
-        
         // Remove build script and source directory.
-        LocalStuff::remove_script(&src_path_str, name).ok();
+        LocalStuff::remove_script(&repo.path, name).ok();
         LocalStuff::delete_dir(src_path).ok();
 
-        // Remove from added list.
-        config.remove_and_save("Added", name).ok();
+        // Remove the repository record.
+        if let Err(e) = Config::transaction(|config| config.remove_repo(name)) {
+            Text::save_error(&e.to_string());
+        }
+        LockFile::record(name).ok();
+    }
+
+    /// Check out every repository at the exact commit pinned in `lubig.lock`.
+    /// Repositories that aren't registered are cloned from their recorded URL first.
+    pub fn sync_locked() {
+        let lock = match LockFile::load() {
+            Ok(lock) => lock,
+            Err(e) => {
+                Text::lockfile_error(&e.to_string());
+                return;
+            }
+        };
+
+        for (name, entry) in &lock.repos {
+            if !Config::load().has_repo(name) {
+                match &entry.url {
+                    Some(url) => { Self::get(url, name, &CloneOptions::default()).ok(); }
+                    None => {
+                        println!("SKIPPED: '{}' is not registered and has no recorded URL", name);
+                        continue;
+                    }
+                }
+            }
+
+            let config = Config::load();
+            let Some(repo) = config.repo(name) else {
+                println!("ERROR: '{}' could not be restored", name);
+                continue;
+            };
+
+            if let Err(e) = RemoteStuff::checkout_commit(&repo.path, &entry.commit, &config.access(name)) {
+                println!("ERROR: '{}' could not be checked out at {}: {}", name, entry.commit, e.message());
+                continue;
+            }
+
+            // The profile script isn't versioned by git, so only warn when it drifted.
+            let profile_hash = LocalStuff::file_hash(Config::load().profile_path(name));
+            if entry.profile_hash.is_some() && profile_hash != entry.profile_hash {
+                println!("WARNING: '{}' profile script differs from the one recorded in lubig.lock", name);
+            }
+
+            LockFile::record(name).ok();
+            println!("SUCCESS: '{}' synced to {}", name, entry.commit);
+        }
+    }
+
+    /// Write a portable manifest of every registered repository to `file`.
+    pub fn export(file: &str, inline: bool) {
+        let config = Config::load();
+        let base = Path::new(file).parent().unwrap_or(Path::new("."));
+        let manifest = Manifest::from_config(&config, inline, base);
+
+        match manifest.save(file) {
+            Ok(()) => println!("SUCCESS: {} repositories exported to '{}'", manifest.repos.len(), file),
+            Err(e) => {
+                println!("ERROR: Unable to write '{}': {}", file, e);
+                return;
+            }
+        }
+
+        // Referenced scripts are read next to the manifest on import: list those to copy there.
+        for (name, entry) in &manifest.repos {
+            let Some(path) = &entry.profile_path else { continue; };
+            if !base.join(path).exists() {
+                println!("WARNING: '{}' profile is referenced as '{}': copy '{}' next to the manifest before importing it.",
+                    name, path, config.profile_path(name).display());
+            }
+        }
+    }
+
+    /// Recreate the repositories described in a manifest: clone missing ones,
+    /// install their profile scripts and restore lock state.
+    /// Repositories that are already registered are skipped.
+    pub fn import(file: &str) {
+        let manifest = match Manifest::load(file) {
+            Ok(m) => m,
+            Err(e) => {
+                println!("ERROR: {}", e);
+                return;
+            }
+        };
+        let base = Path::new(file).parent().unwrap_or(Path::new("."));
+
+        for (name, entry) in &manifest.repos {
+            if Config::load().has_repo(name) {
+                println!("SKIPPED: '{}' is already registered", name);
+                continue;
+            }
+
+            let Some(url) = &entry.url else {
+                println!("SKIPPED: '{}' has no remote URL", name);
+                continue;
+            };
+
+            // Clone the tracked branch as it was cloned originally; the manifest provides
+            // the profile, so no template is suggested.
+            let opts = CloneOptions {
+                depth: entry.depth,
+                branch: entry.branch.clone(),
+                single_branch: entry.single_branch,
+                ..Default::default()
+            };
+            Self::clone_repo(url, name, &opts, false).ok();
+            if !Config::load().has_repo(name) {
+                continue;
+            }
+
+            Self::install_profile(name, entry, base);
+
+            // Reproduce the lock state, tracked branch or tags and labels.
+            Config::transaction(|config| {
+                if let Some(repo) = config.repo_mut(name) {
+                    repo.branch = entry.branch.clone();
+                    repo.tags = entry.tags.clone();
+                    repo.locked = entry.locked;
+                    repo.labels = entry.labels.clone();
+                }
+            }).ok();
+
+            if let Some(pinned) = &entry.pinned {
+                Self::pin(name, pinned);
+            }
+        }
+    }
+
+    /// Install the profile script of a manifest entry, never overwriting an existing one.
+    fn install_profile(name: &str, entry: &ManifestRepo, base: &Path) {
+        let config = Config::load();
+        let default = config.profile_path(name);
+        let target = match &entry.profile_file {
+            Some(file) => default.with_file_name(file),
+            None => default.clone(),
+        };
+
+        if target.exists() {
+            println!("SKIPPED: profile '{}' already exists", target.display());
+            return;
+        }
+
+        let contents = match (&entry.profile, &entry.profile_path) {
+            (Some(inline), _) => Some(inline.clone()),
+            (None, Some(path)) => std::fs::read_to_string(base.join(path)).ok(),
+            (None, None) => None,
+        };
+
+        let Some(contents) = contents else { return; };
+
+        match LocalStuff::write_script(&target, &contents) {
+            Ok(()) if target != default => {
+                println!("WARNING: profile '{}' was written for another OS; '{}' expects '{}'", target.display(), name, default.display());
+            }
+            Ok(()) => println!("'{}' profile installed", name),
+            Err(e) => println!("ERROR: Unable to write profile '{}': {}", target.display(), e),
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+use std::collections::BTreeMap;
+
+use crate::conf::{self, Config, ConfigLock};
+use crate::util::{LocalStuff, RemoteStuff};
+
+/// Contents of `lubig.lock`: the exact state of every registered repository,
+/// used to reproduce a workspace with `lubig sync --locked`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LockFile {
+    #[serde(default)]
+    pub repos: BTreeMap<String, LockedRepo>,
+}
+
+/// Pinned state of a single repository.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LockedRepo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,                   // Remote URL to clone from
+    pub commit: String,                        // Checked-out commit SHA
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_hash: Option<String>,          // Git blob hash of the profile script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,                 // Build output path
+}
+
+impl LockFile {
+    /// Loads `lubig.lock`, returning an empty lockfile if it doesn't exist yet.
+    pub fn load() -> Result<LockFile, Box<dyn std::error::Error>> {
+        let path = lock_path();
+
+        if !path.exists() {
+            return Ok(LockFile::default());
+        }
+
+        let data = fs::read_to_string(&path)?;
+        toml::from_str(&data)
+            .map_err(|e| format!("'{}' could not be read: {}", path.display(), e).into())
+    }
+
+    /// Saves the lockfile atomically.
+    pub fn save(&self) -> std::io::Result<()> {
+        let data = toml::to_string_pretty(self).map_err(std::io::Error::other)?;
+        LocalStuff::write_atomic(lock_path(), &data)
+    }
+
+    /// Records the current state of a registered repository in `lubig.lock`.
+    /// Removes its entry if it is no longer registered.
+    pub fn record(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let _lock = ConfigLock::acquire()?;
+        let config = Config::load();
+        let mut lock = Self::load()?;
+
+        match config.repo(name) {
+            Some(repo) => {
+                let entry = LockedRepo {
+                    url: repo.url.clone(),
+                    commit: RemoteStuff::head_commit(&repo.path)?,
+                    profile_hash: LocalStuff::file_hash(config.profile_path(name)),
+                    build: repo.build.clone(),
+                };
+                lock.repos.insert(name.to_string(), entry);
+            }
+            None => { lock.repos.remove(name); }
+        }
+
+        Ok(lock.save()?)
+    }
+}
+
+/// Returns the path to `lubig.lock`, stored next to the config file.
+pub fn lock_path() -> PathBuf {
+    conf::conf_path().with_file_name("lubig.lock")
+}
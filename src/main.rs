@@ -3,18 +3,36 @@ mod text;   // User-facing messages and help text
 mod util;   // Local and remote utility functions
 mod conf;   // Configuration management
 mod func;   // Core functional operations
+mod lockfile; // Reproducible commit pins (lubig.lock)
+mod manifest; // Portable workspace export/import
+mod auth;   // Credential chain for private remotes
+mod buildlog; // Per-build log files
+mod profile;  // Declarative TOML build profiles
+mod template; // Built-in profile script templates
 
 use std::{env, path::Path};
 
 use text::Text;
-use util::LocalStuff;
+use util::{CloneOptions, LocalStuff, RemoteStuff};
 use conf::Config;
 use func::Execute;
 
 /// Main entry point for the LUBIG CLI.
 fn main() {
     // Collect all command-line arguments into a vector.
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    // Strip the global `--config <file>` option and resolve the workspace location.
+    let mut cli_config = None;
+    if let Some(pos) = args.iter().position(|a| a == "--config") {
+        if pos + 1 >= args.len() {
+            Text::need_args();
+            return;
+        }
+        cli_config = Some(args.remove(pos + 1));
+        args.remove(pos);
+    }
+    conf::init_home(cli_config.as_deref());
 
     // Ensure a config file exists; generate a default one if missing.
     if !conf::conf_path().exists() {
@@ -28,26 +46,73 @@ fn main() {
         Some("add")     => Validate::add(args),
         Some("lock")    => Validate::lock(args),
         Some("unlock")  => Validate::unlock(args),
+        Some("pin")     => Validate::pin(args),
+        Some("auth")    => Validate::auth(args),
+        Some("label")   => Validate::label(args),
         Some("upgrade") => Validate::upgrade(args),
+        Some("outdated") => Validate::outdated(args),
         Some("build")   => Validate::build(args),
+        Some("rollback") => Validate::rollback(args),
+        Some("generations") => Validate::generations(args),
+        Some("switch")  => Validate::switch(args),
+        Some("logs")    => Validate::logs(args),
+        Some("profile") => Validate::profile(args),
         Some("list")    => Validate::list(args),
         Some("status")  => Validate::status(args),
         Some("remove")  => Validate::remove(args),
+        Some("sync")    => Validate::sync(args),
+        Some("export")  => Validate::export(args),
+        Some("import")  => Validate::import(args),
         Some("help")    => Validate::help(args),
         Some(_)         => Text::general_error(), // Unknown command
         None            => Text::need_args(),     // No command provided
     }
 }
 
+/// Resolves a key file argument to an absolute path, so it still works from another directory.
+fn key_path(path: &str) -> Option<String> {
+    match Path::new(path).canonicalize() {
+        Ok(abs) if abs.is_file() => Some(abs.display().to_string()),
+        _ => {
+            Text::error_dir(path);
+            None
+        }
+    }
+}
+
 /// Command validator and dispatcher.
 /// Each method checks argument count/validity before calling the core logic.
 pub struct Validate;
 
 impl Validate {
-    /// Configure directory paths for sources, profiles, or programs.
+    /// Configure directory paths for sources, profiles, or programs, the number of
+    /// build generations and build logs to keep, or show the resolved workspace location.
     pub fn conf(cmd: Vec<String>){
+        if cmd.get(2).map(|s| s.as_str()) == Some("show") {
+            if !LocalStuff::cmd_len(&cmd, 3) { return; }
+            Text::conf_show(conf::home(), &Config::load());
+            return;
+        }
+
         if !LocalStuff::cmd_len(&cmd, 4) { return; }
 
+        // Build generation and build log retention.
+        if cmd[2] == "keep" || cmd[2] == "logs" {
+            match cmd[3].parse::<usize>() {
+                Ok(n) if n > 0 => {
+                    let saved = Config::transaction(|config| match cmd[2].as_str() {
+                        "keep" => config.builds.keep = n,
+                        _      => config.builds.logs = n,
+                    });
+                    if let Err(e) = saved {
+                        Text::save_error(&e.to_string());
+                    }
+                }
+                _ => Text::general_error(),
+            }
+            return;
+        }
+
         // Map shorthand to config keys.
         let next = match cmd.get(2).map(|s| s.as_str()) {
             Some("src")  => "sources",
@@ -63,23 +128,53 @@ impl Validate {
         }
 
         // Update configuration.
-        let mut config = Config::load_config().unwrap_or_default();
-        config.modify_and_save("Directories", next, &cmd[3]).ok();
+        if let Err(e) = Config::transaction(|config| config.set_dir(next, &cmd[3])) {
+            Text::save_error(&e.to_string());
+        }
     }
 
     /// Clone a remote Git repository and register it.
+    /// Accepts `--depth N`, `--branch <ref>`, `--single-branch` and `--key <file>` after the name.
     pub fn get(cmd: Vec<String>){
-        if !LocalStuff::cmd_len(&cmd, 4) { return; }
+        if cmd.len() < 4 {
+            Text::need_args();
+            return;
+        }
+
+        let mut opts = CloneOptions::default();
+        let mut rest = cmd[4..].iter();
+
+        while let Some(flag) = rest.next() {
+            match flag.as_str() {
+                "--depth" => match rest.next().and_then(|n| n.parse::<u32>().ok()).filter(|n| *n > 0) {
+                    Some(n) => opts.depth = Some(n),
+                    None    => { Text::general_error(); return; },
+                },
+                "--branch" => match rest.next() {
+                    Some(b) => opts.branch = Some(b.clone()),
+                    None    => { Text::need_args(); return; },
+                },
+                "--single-branch" => opts.single_branch = true,
+                "--key" => match rest.next() {
+                    Some(k) => match key_path(k) {
+                        Some(k) => opts.key = Some(k),
+                        None    => return,
+                    },
+                    None    => { Text::need_args(); return; },
+                },
+                _ => { Text::general_error(); return; },
+            }
+        }
 
-        let config = Config::load_config().unwrap_or_default();
+        let config = Config::load();
 
         // Prevent duplicate registration.
-        if config.key_exists("Added", &cmd[3]) {
+        if config.has_repo(&cmd[3]) {
             Text::key_exists(&cmd[3]);
             return;
         }
 
-        Execute::get(&cmd[2], &cmd[3]).ok();
+        Execute::get(&cmd[2], &cmd[3], &opts).ok();
     }
 
     /// Register an existing local Git repository.
@@ -98,91 +193,333 @@ impl Validate {
             return;
         }
 
-        let config = Config::load_config().unwrap_or_default();
+        let config = Config::load();
 
         // Prevent duplicate registration.
-        if config.key_exists("Added", &cmd[3]) {
+        if config.has_repo(&cmd[3]) {
             Text::key_exists(&cmd[3]);
             return;
         }
 
-        Execute::add(&cmd[2], &cmd[3]);
+        Execute::add(&cmd[2], &cmd[3], None, &CloneOptions::default());
     }
 
     /// Lock a registered repository to prevent updates.
     pub fn lock(cmd: Vec<String>) {
         if !LocalStuff::cmd_len(&cmd, 3) { return; }
 
-        let mut config = Config::load_config().unwrap_or_default();
-
-        if !config.key_exists("Added", &cmd[2]) {
-            Text::key_doesnt_exists(&cmd[2]);
-            return;
-        }
+        // Lock it, remembering whether it already was.
+        let was_locked = Config::transaction(|config| {
+            config.repo_mut(&cmd[2]).map(|repo| std::mem::replace(&mut repo.locked, true))
+        });
 
-        // If currently unlocked, remove from unlocked list.
-        if config.key_exists("Unlocked", &cmd[2]) {
-            config.remove_and_save("Unlocked", &cmd[2]).ok();
-            println!("SUCCESS: '{}' was lock for updates.", &cmd[2]);
-        } else {
-            println!("Error: '{}' is already locked for updates.", &cmd[2]);
+        match was_locked {
+            Ok(None)        => Text::key_doesnt_exists(&cmd[2]),
+            Ok(Some(false)) => println!("SUCCESS: '{}' was lock for updates.", &cmd[2]),
+            Ok(Some(true))  => println!("Error: '{}' is already locked for updates.", &cmd[2]),
+            Err(e)          => Text::save_error(&e.to_string()),
         }
     }
 
-    /// Unlock a repository for updates, optionally specifying a branch.
+    /// Unlock a repository for updates, optionally specifying a branch,
+    /// or a tag pattern (`--tags <glob | semver range>`) to track releases only.
     pub fn unlock(cmd: Vec<String>) {
         if cmd.len() < 3 {
             Text::need_args(); 
             return; 
-        } else if cmd.len() > 4 {
+        } else if cmd.len() > 5 || (cmd.len() == 5 && cmd[3] != "--tags") {
             Text::exceed_args();
             return;
         }
 
-        // Default branch is "main" if not provided.
-        let branch = match cmd.get(3).map(|s| s.as_str()) {
-            Some(_) => &cmd[3],
-            None    => "main",
+        // Tag mode tracks a pattern; otherwise the given branch, or the one already
+        // recorded (e.g. by `get --branch`), falling back to "main".
+        let tags = match cmd.get(3).map(|s| s.as_str()) {
+            Some("--tags") => match cmd.get(4) {
+                Some(pattern) => Some(pattern.clone()),
+                None          => { Text::need_args(); return; },
+            },
+            _ => None,
         };
+        let given = cmd.get(3).filter(|_| tags.is_none());
+
+        let found = Config::transaction(|config| config.repo_mut(&cmd[2]).map(|repo| {
+            if tags.is_none() {
+                let branch = given.or(repo.branch.as_ref()).filter(|b| !b.is_empty());
+                repo.branch = Some(branch.map_or("main", |b| b.as_str()).to_string());
+            }
+            repo.tags = tags.clone();
+            repo.pinned = None;
+            repo.locked = false;
+            repo.branch.clone().unwrap_or_default()
+        }));
+
+        match (found, &tags) {
+            (Ok(Some(branch)), None)     => println!("SUCCESS: '{}' was unlock for updates. From branch: '{}'", &cmd[2], branch),
+            (Ok(Some(_)), Some(pattern)) => println!("SUCCESS: '{}' was unlock for updates. Tracking tags: '{}'", &cmd[2], pattern),
+            (Ok(None), _)                => Text::key_doesnt_exists(&cmd[2]),
+            (Err(e), _)                  => Text::save_error(&e.to_string()),
+        }
+    }
+
+    /// Pin a repository at a specific commit, tag or branch.
+    pub fn pin(cmd: Vec<String>) {
+        if !LocalStuff::cmd_len(&cmd, 4) { return; }
 
-        let mut config = Config::load_config().unwrap_or_default();
+        let config = Config::load();
 
-        if !config.key_exists("Added", &cmd[2]) {
+        if !config.has_repo(&cmd[2]) {
             Text::key_doesnt_exists(&cmd[2]);
             return;
         }
 
-        config.modify_and_save("Unlocked", &cmd[2], branch).ok();
-        println!("SUCCESS: '{}' was unlock for updates. From branch: '{}'", &cmd[2], branch);
+        Execute::pin(&cmd[2], &cmd[3]);
+    }
+
+    /// Set or clear the labels used to select repositories (`upgrade --label`).
+    pub fn label(cmd: Vec<String>) {
+        if cmd.len() < 4 {
+            Text::need_args();
+            return;
+        }
+
+        let labels: Vec<String> = match cmd[3].as_str() {
+            "--clear" if cmd.len() == 4 => Vec::new(),
+            "--clear" => { Text::exceed_args(); return; },
+            _ => cmd[3..].to_vec(),
+        };
+
+        let found = Config::transaction(|config| match config.repo_mut(&cmd[2]) {
+            Some(repo) => { repo.labels = labels.clone(); true }
+            None => false,
+        });
+
+        match found {
+            Ok(true) if labels.is_empty() => println!("SUCCESS: '{}' labels cleared", &cmd[2]),
+            Ok(true)  => println!("SUCCESS: '{}' labelled: {}", &cmd[2], labels.join(", ")),
+            Ok(false) => Text::key_doesnt_exists(&cmd[2]),
+            Err(e)    => Text::save_error(&e.to_string()),
+        }
+    }
+
+    /// Set or clear the SSH key offered to a repository's remote (or to every remote).
+    pub fn auth(cmd: Vec<String>) {
+        if !LocalStuff::cmd_len(&cmd, 4) { return; }
+
+        let key = match cmd[3].as_str() {
+            "--clear" => None,
+            path => match key_path(path) {
+                Some(path) => Some(path),
+                None       => return,
+            },
+        };
+        let target = if cmd[2] == "--global" { "every remote".to_string() } else { format!("'{}'", cmd[2]) };
+
+        let found = Config::transaction(|config| {
+            if cmd[2] == "--global" {
+                config.auth.ssh_key = key.clone();
+                return true;
+            }
+            match config.repo_mut(&cmd[2]) {
+                Some(repo) => { repo.ssh_key = key.clone(); true }
+                None => false,
+            }
+        });
+
+        match (found, &key) {
+            (Ok(true), Some(path)) => println!("SUCCESS: {} will be fetched with key '{}'", target, path),
+            (Ok(true), None)       => println!("SUCCESS: {} key cleared", target),
+            (Ok(false), _)         => Text::key_doesnt_exists(&cmd[2]),
+            (Err(e), _)            => Text::save_error(&e.to_string()),
+        }
     }
 
     /// Upgrade all unlocked repositories.
+    /// Optional names or globs, and `--label <label>`, restrict it to a subset.
+    /// Accepts `--jobs N` to fetch up to N repositories concurrently (1 by default)
+    /// `--fail-fast` to stop after the first failure and `--force` to rebuild unchanged
+    /// repositories. Exits non-zero when anything failed.
     pub fn upgrade(cmd: Vec<String>){
-        if !LocalStuff::cmd_len(&cmd, 2) { return; }
-        Execute::upgrade();
+        let mut jobs = 1;
+        let mut fail_fast = false;
+        let mut force = false;
+        let mut patterns = Vec::new();
+        let mut labels = Vec::new();
+        let mut rest = cmd[2..].iter();
+
+        while let Some(flag) = rest.next() {
+            match flag.as_str() {
+                "--jobs" => match rest.next().map(|n| n.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => jobs = n,
+                    Some(_) => { Text::general_error(); return; },
+                    None    => { Text::need_args(); return; },
+                },
+                "--fail-fast" => fail_fast = true,
+                "--force" => force = true,
+                "--label" => match rest.next() {
+                    Some(label) => labels.push(label.clone()),
+                    None        => { Text::need_args(); return; },
+                },
+                flag if flag.starts_with("--") => { Text::general_error(); return; },
+                pattern => patterns.push(pattern.to_string()),
+            }
+        }
+
+        // Resolve the selection up front so a typo doesn't upgrade anything.
+        let only = match (patterns.is_empty() && labels.is_empty(), Config::load().select(&patterns, &labels)) {
+            (true, _)       => None,
+            (false, Ok(s))  => Some(s),
+            (false, Err(e)) => { println!("ERROR: {}", e); std::process::exit(1); },
+        };
+
+        if !Execute::upgrade(only.as_ref(), jobs, fail_fast, force) {
+            std::process::exit(1);
+        }
     }
 
-    /// Build a specific registered repository.
+    /// Show pending upstream commits for every unlocked repository.
+    pub fn outdated(cmd: Vec<String>){
+        let json = match cmd.get(2).map(|s| s.as_str()) {
+            None           => false,
+            Some("--json") => true,
+            Some(_)        => { Text::general_error(); return; },
+        };
+
+        if !LocalStuff::cmd_len(&cmd, if json { 3 } else { 2 }) { return; }
+        Execute::outdated(json);
+    }
+
+    /// Build a specific registered repository (`--force` rebuilds even when nothing changed).
     pub fn build(cmd: Vec<String>){
+        if cmd.len() < 3 {
+            Text::need_args();
+            return;
+        } else if cmd.len() > 4 {
+            Text::exceed_args();
+            return;
+        }
+
+        let force = match cmd.get(3).map(|s| s.as_str()) {
+            None            => false,
+            Some("--force") => true,
+            Some(_)         => { Text::general_error(); return; },
+        };
+
+        let config = Config::load();
+
+        if !config.has_repo(&cmd[2]) {
+            Text::key_doesnt_exists(&cmd[2]);
+            return;
+        }
+
+        if let Err(e) = Execute::build(&cmd[2], force) {
+            println!("ERROR: Failed to build '{}': {}", &cmd[2], e);
+        }
+    }
+
+    /// List the build generations of a repository.
+    pub fn generations(cmd: Vec<String>){
         if !LocalStuff::cmd_len(&cmd, 3) { return; }
 
-        let config = Config::load_config().unwrap_or_default();
+        if !Config::load().has_repo(&cmd[2]) {
+            Text::key_doesnt_exists(&cmd[2]);
+            return;
+        }
 
-        if !config.key_exists("Added", &cmd[2]) {
+        Execute::generations(&cmd[2]);
+    }
+
+    /// Show the build logs of a repository (`--last` by default, `--list` or `--failed`).
+    pub fn logs(cmd: Vec<String>){
+        if cmd.len() < 3 {
+            Text::need_args();
+            return;
+        } else if cmd.len() > 4 {
+            Text::exceed_args();
+            return;
+        }
+
+        let mode = cmd.get(3).map(|s| s.as_str()).unwrap_or("--last");
+        if !["--last", "--list", "--failed"].contains(&mode) {
+            Text::general_error();
+            return;
+        }
+
+        if !Config::load().has_repo(&cmd[2]) {
+            Text::key_doesnt_exists(&cmd[2]);
+            return;
+        }
+
+        Execute::logs(&cmd[2], mode);
+    }
+
+    /// Create a profile script from a template: `profile init <name> [--template <kind>]`.
+    pub fn profile(cmd: Vec<String>){
+        if cmd.len() < 4 {
+            Text::need_args();
+            return;
+        } else if cmd.len() > 6 {
+            Text::exceed_args();
+            return;
+        }
+
+        if cmd[2] != "init" {
+            Text::general_error();
+            return;
+        }
+
+        let template = match (cmd.get(4).map(|s| s.as_str()), cmd.get(5)) {
+            (None, _)                        => None,
+            (Some("--template"), Some(kind)) => Some(kind.as_str()),
+            (Some("--template"), None)       => { Text::need_args(); return; },
+            _                                => { Text::general_error(); return; },
+        };
+
+        Execute::profile_init(&cmd[3], template);
+    }
+
+    /// Make another build generation of a repository the current one.
+    pub fn switch(cmd: Vec<String>){
+        if !LocalStuff::cmd_len(&cmd, 4) { return; }
+
+        if !Config::load().has_repo(&cmd[2]) {
+            Text::key_doesnt_exists(&cmd[2]);
+            return;
+        }
+
+        Execute::switch(&cmd[2], &cmd[3]);
+    }
+
+    /// Roll a repository back to the commit it had before its last upgrade(s).
+    pub fn rollback(cmd: Vec<String>){
+        let steps = match cmd.len() {
+            3 => 1,
+            5 if cmd[3] == "--steps" => match cmd[4].parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => { Text::general_error(); return; },
+            },
+            n if n < 3 => { Text::need_args(); return; },
+            n if n > 5 => { Text::exceed_args(); return; },
+            _ => { Text::general_error(); return; },
+        };
+
+        let config = Config::load();
+
+        if !config.has_repo(&cmd[2]) {
             Text::key_doesnt_exists(&cmd[2]);
             return;
         }
 
-        Execute::build(&cmd[2]).ok();
+        Execute::rollback(&cmd[2], steps);
     }
 
     /// List all registered repositories.
     pub fn list(cmd: Vec<String>){
         if !LocalStuff::cmd_len(&cmd, 2) { return; }
         
-        let config = Config::load_config().unwrap_or_default();
+        let config = Config::load();
         
-        for key in config.added.keys() {
+        for key in config.repos.keys() {
             println!("{}", key);
         }
     }
@@ -191,37 +528,40 @@ impl Validate {
     pub fn status(cmd: Vec<String>){
         if !LocalStuff::cmd_len(&cmd, 3) { return; }
         
-        let config = Config::load_config().unwrap_or_default();
+        let config = Config::load();
         
-        if !config.key_exists("Added", &cmd[2]) {
+        if !config.has_repo(&cmd[2]) {
             Text::key_doesnt_exists(&cmd[2]);
             return;
         }
 
-        // Determine lock state.
-        let locked = if config.key_exists("Unlocked", &cmd[2]) {
-            "false"
-        } else {
-            "true"
-        };
+        let repo = config.repo(&cmd[2]).unwrap();
 
-        // Determine build state.
-        let build = if config.key_exists("Build", &cmd[2]) {
-            "true"
-        } else {
-            "false"
-        };
+        print!("'{}' state is: lock = {}, build = {}", &cmd[2], repo.locked, repo.build.is_some());
 
-        println!("'{}' state is: lock = {}, build = {}", &cmd[2], locked, build);
+        if let Some(pinned) = &repo.pinned {
+            print!(", pinned = {}", pinned);
+        }
+
+        if !repo.labels.is_empty() {
+            print!(", labels = {}", repo.labels.join(","));
+        }
+
+        // Tag mode: show the tracked pattern and the tag currently checked out.
+        if let Some(pattern) = &repo.tags {
+            let tag = RemoteStuff::head_tag(&repo.path).unwrap_or_else(|| "none".into());
+            print!(", tags = '{}', tag = {}", pattern, tag);
+        }
+        println!();
     }
 
     /// Remove a registered repository and its builds.
     pub fn remove(cmd: Vec<String>){
-        let config = Config::load_config().unwrap_or_default();
+        let config = Config::load();
 
         if !LocalStuff::cmd_len(&cmd, 3) { return; }
 
-        if !config.key_exists("Added", &cmd[2]) {
+        if !config.has_repo(&cmd[2]) {
             Text::key_doesnt_exists(&cmd[2]);
             return;
         }
@@ -229,6 +569,51 @@ impl Validate {
         Execute::remove(&cmd[2]);
     }
 
+    /// Reproduce the workspace pinned in `lubig.lock`.
+    pub fn sync(cmd: Vec<String>){
+        if !LocalStuff::cmd_len(&cmd, 3) { return; }
+
+        // Exact commits are currently the only sync mode.
+        if cmd[2] != "--locked" {
+            Text::general_error();
+            return;
+        }
+
+        Execute::sync_locked();
+    }
+
+    /// Export the workspace to a portable manifest, optionally referencing
+    /// profile scripts instead of inlining them.
+    pub fn export(cmd: Vec<String>){
+        if cmd.len() < 3 {
+            Text::need_args();
+            return;
+        } else if cmd.len() > 4 {
+            Text::exceed_args();
+            return;
+        }
+
+        let inline = match cmd.get(3).map(|s| s.as_str()) {
+            None                => true,
+            Some("--reference") => false,
+            Some(_)             => { Text::general_error(); return; },
+        };
+
+        Execute::export(&cmd[2], inline);
+    }
+
+    /// Recreate repositories from a manifest produced by `export`.
+    pub fn import(cmd: Vec<String>){
+        if !LocalStuff::cmd_len(&cmd, 3) { return; }
+
+        if !Path::new(&cmd[2]).exists() {
+            Text::error_dir(&cmd[2]);
+            return;
+        }
+
+        Execute::import(&cmd[2]);
+    }
+
     /// Display the help text.
     pub fn help(cmd: Vec<String>){
         if !LocalStuff::cmd_len(&cmd, 2) { return; }
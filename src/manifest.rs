@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+use std::collections::BTreeMap;
+
+use crate::conf::Config;
+use crate::util::LocalStuff;
+
+/// Portable description of a workspace, used to recreate it on another machine
+/// with `lubig export` / `lubig import`. Holds no machine-specific paths.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub repos: BTreeMap<String, ManifestRepo>,
+}
+
+/// Portable description of a single repository.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ManifestRepo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,                   // Remote URL to clone from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,                // Tracked ref for upgrades
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,                  // Tag mode: glob or semver range to track
+    #[serde(default = "locked_default")]
+    pub locked: bool,                          // Lock state to reproduce
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<String>,                // Commit the repo is pinned at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,                    // Shallow clone depth
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub single_branch: bool,                   // Clone only the tracked branch
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,                   // Labels used to select repositories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_file: Option<String>,          // Script file name, e.g. `name.sh`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,               // Inlined script contents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_path: Option<String>,          // Referenced script, relative to the manifest
+}
+
+fn locked_default() -> bool {
+    true
+}
+
+impl Manifest {
+    /// Builds a manifest from the current configuration.
+    /// Profile scripts are inlined, or referenced when `inline` is false: by their path
+    /// relative to `dir` (the manifest's directory) when they live under it, otherwise by
+    /// file name, expecting them to be shipped next to the manifest.
+    pub fn from_config(config: &Config, inline: bool, dir: &Path) -> Manifest {
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+
+        let mut manifest = Manifest::default();
+
+        for (name, repo) in &config.repos {
+            let script = config.profile_path(name);
+            let mut entry = ManifestRepo {
+                url: repo.url.clone(),
+                branch: repo.branch.clone(),
+                tags: repo.tags.clone(),
+                locked: repo.locked,
+                pinned: repo.pinned.clone(),
+                depth: repo.depth,
+                single_branch: repo.single_branch,
+                labels: repo.labels.clone(),
+                ..ManifestRepo::default()
+            };
+
+            if script.exists() {
+                entry.profile_file = script.file_name().map(|f| f.to_string_lossy().into_owned());
+
+                if inline {
+                    entry.profile = fs::read_to_string(&script).ok();
+                } else {
+                    let relative = script.strip_prefix(&dir).ok().or_else(|| script.file_name().map(Path::new));
+                    entry.profile_path = relative.map(|p| p.to_string_lossy().into_owned());
+                }
+            }
+
+            manifest.repos.insert(name.clone(), entry);
+        }
+
+        manifest
+    }
+
+    /// Loads a manifest from a TOML file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Manifest, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)?;
+
+        toml::from_str(&data)
+            .map_err(|e| format!("'{}' is not a valid manifest: {}", path.display(), e).into())
+    }
+
+    /// Saves the manifest to a TOML file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let data = toml::to_string_pretty(self).map_err(std::io::Error::other)?;
+        LocalStuff::write_atomic(path, &data)
+    }
+}
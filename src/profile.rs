@@ -0,0 +1,225 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, env, fs, path::Path, process::Command};
+
+/// Placeholder replaced by the output directory in arguments, environment values
+/// and working directories. The same directory is passed to shell scripts as `$1`.
+const OUT: &str = "{out}";
+
+/// A declarative build profile (`profiles/<name>.toml`): ordered steps run directly
+/// by lubig, so one profile works on every platform.
+///
+/// ```toml
+/// workdir = "."
+///
+/// [env]
+/// CARGO_TARGET_DIR = "target"
+///
+/// [[steps]]
+/// program = "cargo"
+/// args = ["build", "--release"]
+///
+/// [[steps]]
+/// program = "cp"
+/// args = ["-r", "target/release", "{out}/myproject"]
+///
+/// [os.windows]
+/// steps = [
+///     { program = "cargo", args = ["build", "--release"] },
+///     { program = "xcopy", args = ["/E", "/I", "target\\release", "{out}\\myproject"] },
+/// ]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub workdir: Option<String>,               // Relative to the source directory
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,         // Set for every step
+    #[serde(default)]
+    pub steps: Vec<Step>,                      // Run in order; the build stops at the first failure
+    #[serde(default)]
+    pub os: BTreeMap<String, Overrides>,       // Keyed by `linux`, `macos`, `windows`...
+}
+
+/// One program invocation of a profile.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub workdir: Option<String>,               // Overrides the profile working directory
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,         // Added to the profile environment
+}
+
+/// Per-OS changes to a profile: `steps` and `workdir` replace the base ones,
+/// `env` entries are added to (or override) the base environment.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Overrides {
+    pub workdir: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub steps: Option<Vec<Step>>,
+}
+
+impl Profile {
+    /// Reads and validates a TOML profile.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Profile, String> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)
+            .map_err(|e| format!("'{}' could not be read: {}", path.display(), e))?;
+        let profile: Profile = toml::from_str(&data)
+            .map_err(|e| format!("'{}' is not a valid profile: {}", path.display(), e))?;
+
+        if profile.resolve().0.is_empty() {
+            return Err(format!("'{}' has no steps for {}", path.display(), env::consts::OS));
+        }
+        Ok(profile)
+    }
+
+    /// Applies the overrides for the running OS: returns the steps, working directory
+    /// and environment to use.
+    fn resolve(&self) -> (Vec<Step>, Option<String>, BTreeMap<String, String>) {
+        let mut steps = self.steps.clone();
+        let mut workdir = self.workdir.clone();
+        let mut env = self.env.clone();
+
+        if let Some(os) = self.os.get(env::consts::OS) {
+            if let Some(os_steps) = &os.steps {
+                steps = os_steps.clone();
+            }
+            if os.workdir.is_some() {
+                workdir = os.workdir.clone();
+            }
+            env.extend(os.env.clone());
+        }
+        (steps, workdir, env)
+    }
+
+    /// Builds the commands of every step, run from `src` and writing into `out`.
+    /// `{out}` is substituted and exported as `LUBIG_OUT`.
+    pub fn commands(&self, src: &Path, out: &Path) -> Vec<Command> {
+        let out_str = out.to_string_lossy();
+        let fill = |value: &str| value.replace(OUT, &out_str);
+        let (steps, workdir, env) = self.resolve();
+
+        steps.iter().map(|step| {
+            let mut command = Command::new(fill(&step.program));
+            command.args(step.args.iter().map(|a| fill(a)));
+
+            let dir = step.workdir.as_ref().or(workdir.as_ref());
+            command.current_dir(dir.map(|d| src.join(fill(d))).unwrap_or_else(|| src.to_path_buf()));
+
+            command.env("LUBIG_OUT", out);
+            for (key, value) in env.iter().chain(&step.env) {
+                command.env(key, fill(value));
+            }
+            command
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    fn parse(toml: &str) -> Profile {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn programs(steps: &[Step]) -> Vec<&str> {
+        steps.iter().map(|s| s.program.as_str()).collect()
+    }
+
+    #[test]
+    fn other_os_overrides_are_ignored() {
+        let profile = parse(r#"
+            workdir = "src"
+            env = { MODE = "release" }
+            steps = [{ program = "make" }]
+
+            [os.not-this-os]
+            workdir = "elsewhere"
+            env = { MODE = "debug" }
+            steps = [{ program = "nmake" }]
+        "#);
+        let (steps, workdir, env) = profile.resolve();
+
+        assert_eq!(programs(&steps), ["make"]);
+        assert_eq!(workdir.as_deref(), Some("src"));
+        assert_eq!(env.get("MODE").map(String::as_str), Some("release"));
+    }
+
+    #[test]
+    fn current_os_overrides_replace_and_merge() {
+        let profile = parse(&format!(r#"
+            workdir = "src"
+            env = {{ MODE = "release", KEEP = "yes" }}
+            steps = [{{ program = "make" }}, {{ program = "make", args = ["install"] }}]
+
+            [os.{}]
+            workdir = "build"
+            env = {{ MODE = "debug", EXTRA = "1" }}
+            steps = [{{ program = "ninja" }}]
+        "#, env::consts::OS));
+        let (steps, workdir, env) = profile.resolve();
+
+        assert_eq!(programs(&steps), ["ninja"]);
+        assert_eq!(workdir.as_deref(), Some("build"));
+        assert_eq!(env.get("MODE").map(String::as_str), Some("debug"));
+        assert_eq!(env.get("KEEP").map(String::as_str), Some("yes"));
+        assert_eq!(env.get("EXTRA").map(String::as_str), Some("1"));
+    }
+
+    #[test]
+    fn override_without_steps_keeps_the_base_steps() {
+        let profile = parse(&format!(r#"
+            steps = [{{ program = "cargo" }}]
+
+            [os.{}]
+            env = {{ CC = "clang" }}
+        "#, env::consts::OS));
+        let (steps, workdir, env) = profile.resolve();
+
+        assert_eq!(programs(&steps), ["cargo"]);
+        assert_eq!(workdir, None);
+        assert_eq!(env.get("CC").map(String::as_str), Some("clang"));
+    }
+
+    #[test]
+    fn commands_substitute_the_output_directory() {
+        let profile = parse(r#"
+            workdir = "{out}/tmp"
+            env = { PREFIX = "{out}/usr" }
+
+            [[steps]]
+            program = "{out}/bin/tool"
+            args = ["--prefix", "{out}", "plain"]
+
+            [[steps]]
+            program = "make"
+            workdir = "sub"
+            env = { STEP = "{out}" }
+        "#);
+        let (src, out) = (Path::new("/src"), Path::new("/out"));
+        let commands = profile.commands(src, out);
+        let env = |command: &Command, key: &str| {
+            command.get_envs().find(|(k, _)| *k == key).and_then(|(_, v)| v.map(OsStr::to_os_string))
+        };
+
+        let first = &commands[0];
+        assert_eq!(first.get_program(), "/out/bin/tool");
+        assert_eq!(first.get_args().collect::<Vec<_>>(), ["--prefix", "/out", "plain"]);
+        assert_eq!(first.get_current_dir(), Some(Path::new("/out/tmp")));   // an absolute workdir replaces the source one
+        assert_eq!(env(first, "PREFIX"), Some("/out/usr".into()));
+        assert_eq!(env(first, "LUBIG_OUT"), Some("/out".into()));
+        assert_eq!(env(first, "STEP"), None);
+
+        let second = &commands[1];
+        assert_eq!(second.get_current_dir(), Some(Path::new("/src/sub")));
+        assert_eq!(env(second, "PREFIX"), Some("/out/usr".into()));
+        assert_eq!(env(second, "STEP"), Some("/out".into()));
+    }
+}
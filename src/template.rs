@@ -0,0 +1,136 @@
+use std::path::Path;
+
+/// Built-in profile templates for `lubig profile init`.
+/// Each template is a list of commands in which `{out}` stands for the install
+/// directory, `<first argument>/<name>`; they are rendered as a `.sh` script on
+/// Unix and a `.bat` script on Windows. A command written `?<file> <command>` only
+/// runs when `<file>` is missing from the source tree.
+pub const TEMPLATES: [(&str, &[&str]); 7] = [
+    ("cargo", &[
+        "cargo install --locked --path . --root {out}",
+    ]),
+    ("cmake", &[
+        "cmake -S . -B build -DCMAKE_BUILD_TYPE=Release -DCMAKE_INSTALL_PREFIX={out}",
+        "cmake --build build --config Release",
+        "cmake --install build --config Release",
+    ]),
+    ("meson", &[
+        "meson setup build --buildtype=release --prefix={out} --reconfigure",
+        "meson compile -C build",
+        "meson install -C build",
+    ]),
+    ("make", &[
+        "make",
+        "make install PREFIX={out}",
+    ]),
+    ("autotools", &[
+        "?configure autoreconf -fi",
+        "./configure --prefix={out}",
+        "make",
+        "make install",
+    ]),
+    ("go", &[
+        "go build -o {out}/bin/ ./...",
+    ]),
+    ("npm", &[
+        "npm ci",
+        "npm run build --if-present",
+        "npm install --global --install-links --prefix {out} .",
+    ]),
+];
+
+/// Files that identify a build system, checked in order: the first one present in the
+/// source tree picks the template (`configure` before `Makefile`, which it generates).
+const MARKERS: [(&str, &str); 11] = [
+    ("Cargo.toml", "cargo"),
+    ("meson.build", "meson"),
+    ("CMakeLists.txt", "cmake"),
+    ("go.mod", "go"),
+    ("package.json", "npm"),
+    ("configure", "autotools"),
+    ("configure.ac", "autotools"),
+    ("configure.in", "autotools"),
+    ("GNUmakefile", "make"),
+    ("Makefile", "make"),
+    ("makefile", "make"),
+];
+
+/// Detects the build system of a source tree from its top-level files.
+/// Returns the matching template name and the file that identified it.
+pub fn detect<P: AsRef<Path>>(src: P) -> Option<(&'static str, &'static str)> {
+    MARKERS.iter()
+        .find(|(file, _)| src.as_ref().join(file).is_file())
+        .map(|(file, template)| (*template, *file))
+}
+
+/// Returns the names of every template, for error messages and help.
+pub fn names() -> Vec<&'static str> {
+    TEMPLATES.iter().map(|(name, _)| *name).collect()
+}
+
+/// Renders the profile script of `template` for repository `name`, or a skeleton to fill
+/// in when no template is given. Returns `None` for an unknown template.
+pub fn render(template: Option<&str>, name: &str) -> Option<String> {
+    let commands: &[&str] = match template {
+        Some(template) => TEMPLATES.iter().find(|(t, _)| *t == template)?.1,
+        None => &[],
+    };
+    let label = template.unwrap_or("custom");
+
+    Some(if cfg!(windows) { render_bat(label, name, commands) } else { render_sh(label, name, commands) })
+}
+
+/// Renders a POSIX shell script that stops at the first failing command.
+fn render_sh(label: &str, name: &str, commands: &[&str]) -> String {
+    let mut script = format!(
+        "#!/bin/sh\n# lubig build profile for '{name}' ({label} template).\n\
+         # Runs from the repository source directory; $1 is the directory to build into.\n\
+         # Install the program in \"$1/{name}\": lubig turns it into a new build generation.\n\
+         set -e\n\nOUT=\"$1/{name}\"\n\n"
+    );
+
+    if commands.is_empty() {
+        // Fail until filled in, so an empty generation is never installed.
+        script += "# TODO: build the program and install it into \"$OUT\".\nexit 1\n";
+    }
+    for command in commands {
+        let (unless, command) = split_guard(command);
+        if let Some(file) = unless {
+            script += &format!("[ -e ./{} ] || ", file);
+        }
+        script += &command.replace("{out}", "\"$OUT\"");
+        script.push('\n');
+    }
+    script
+}
+
+/// Renders a Windows batch script that stops at the first failing command.
+fn render_bat(label: &str, name: &str, commands: &[&str]) -> String {
+    let mut script = format!(
+        "@echo off\r\nrem lubig build profile for '{name}' ({label} template).\r\n\
+         rem Runs from the repository source directory; %1 is the directory to build into.\r\n\
+         rem Install the program in \"%~1\\{name}\": lubig turns it into a new build generation.\r\n\r\n\
+         set \"OUT=%~1\\{name}\"\r\n\r\n"
+    );
+
+    if commands.is_empty() {
+        script += "rem TODO: build the program and install it into \"%OUT%\".\r\nexit /b 1\r\n";
+    }
+    for command in commands {
+        let (unless, command) = split_guard(command);
+        if let Some(file) = unless {
+            script += &format!("if not exist {} ", file);
+        }
+        // `call` keeps the script running after `.cmd` tools such as npm.
+        script += &format!("call {}\r\nif errorlevel 1 exit /b 1\r\n", command.replace("{out}", "\"%OUT%\""));
+    }
+    script
+}
+
+/// Splits a `?<file> <command>` template command into the file and the command.
+fn split_guard(command: &str) -> (Option<&str>, &str) {
+    match command.strip_prefix('?').and_then(|c| c.split_once(' ')) {
+        Some((file, command)) => (Some(file), command),
+        None => (None, command),
+    }
+}
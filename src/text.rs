@@ -1,3 +1,14 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::buildlog::LogEntry;
+use crate::conf::{Config, Generation, Home};
+use crate::util::{ChangeEntry, LocalStuff, Pending, PullOutcome, UpgradeStatus};
+
+/// Whether transfer and checkout progress is drawn. Turned off while several
+/// fetches run at once, since their single-line progress would overwrite each other.
+static PROGRESS: AtomicBool = AtomicBool::new(true);
+
 /// `Text` is a utility struct containing only static methods.
 /// It centralizes all user-facing output messages, ensuring consistency
 /// and making it easier to maintain or localize in the future.
@@ -17,11 +28,17 @@ impl Text
         println!("      | lubig conf src <path>");
         println!("      | lubig conf prof <path>");
         println!("      | lubig conf prog <path>");
+        println!("      | lubig conf keep <N> (build generations kept per program, 3 by default)");
+        println!("      | lubig conf logs <N> (build logs kept per repository, 10 by default)");
+        println!("      | lubig conf show (prints the config file in use and every configured path)");
+        println!("      | The config file is resolved from: --config <file>, $LUBIG_HOME, an existing config next to the executable, then XDG dirs.");
         
         // Clone and register remote repository
         println!("  get: Use it to clone and add git repositories to lubig registries.");
         println!("      | Example:");
         println!("      | lubig get <https://url.com/wanted/repository.git> <custom_name>");
+        println!("      | Options: --depth <N> (shallow clone, kept shallow on upgrade), --branch <ref>, --single-branch, --key <ssh_key_file>");
+        println!("      | lubig get <https://url.com/wanted/repository.git> <custom_name> --depth 1 --branch <ref> --single-branch");
         
         // Register local repository
         println!("  add: Use it to add local git cloned repositories to lubig registries.");
@@ -32,17 +49,84 @@ impl Text
         println!("  lock/unlock: Use it to lock or unlock updates to a specific registered repository.");
         println!("      | Examples:");
         println!("      | lubig lock <registered_repository_name>");
-        println!("      | lubig unlock <registered_repository_name> (optional)<repository_branch_line> ('main' by default)");
+        println!("      | lubig unlock <registered_repository_name> (optional)<repository_branch_line> (the recorded branch, else 'main')");
+        println!("      | lubig unlock <registered_repository_name> --tags <'v*' | ^1.4> (track release tags: a glob or a semver range)");
+        
+        // Pin a repository at an exact commit
+        println!("  pin: Use it to check out a specific commit, tag or branch (detached) and lock the repository at it.");
+        println!("      | upgrade and build warn when the worktree no longer matches the pin. 'unlock' removes the pin.");
+        println!("      | Example:");
+        println!("      | lubig pin <registered_repository_name> <commit | tag | branch>");
+        
+        // Credentials for private remotes
+        println!("  auth: Use it to set the SSH private key offered when fetching a repository, or every repository (--global).");
+        println!("      | Fetches try ssh-agent, the repository key, the global key, ~/.ssh/id_* keys, then git credential helpers (HTTPS).");
+        println!("      | Examples:");
+        println!("      | lubig auth <registered_repository_name> <key_file>");
+        println!("      | lubig auth --global <key_file>");
+        println!("      | lubig auth <registered_repository_name | --global> --clear");
+        
+        // Label repositories for selection
+        println!("  label: Use it to set the labels of a registered repository, to select it with 'upgrade --label'.");
+        println!("      | Examples:");
+        println!("      | lubig label <registered_repository_name> <label>...");
+        println!("      | lubig label <registered_repository_name> --clear");
         
         // Upgrade all unlocked repositories
         println!("  upgrade: Use it to upgrade every registered and unlocked repository.");
-        println!("      | Example:");
+        println!("      | Prints a changelog of the applied commits and keeps it in history/<name>.log.");
+        println!("      | --jobs <N> fetches up to N repositories at once; rebuilds still run one at a time.");
+        println!("      | Failures don't stop the other repositories. Ends with a summary table and exits non-zero if anything failed or was refused (diverged, dirty worktree, no matching tag).");
+        println!("      | --fail-fast stops starting new fetches and rebuilds after the first failure.");
+        println!("      | Unchanged repositories aren't rebuilt; --force rebuilds every selected repository with a build.");
+        println!("      | Names, globs or --label <label> upgrade only a subset of the repositories.");
+        println!("      | Examples:");
         println!("      | lubig upgrade");
+        println!("      | lubig upgrade --jobs 8 --fail-fast");
+        println!("      | lubig upgrade <registered_repository_name>... (optional)--label <label>");
+        println!("      | lubig upgrade 'tool-*'");
+        
+        // Preview pending upstream changes
+        println!("  outdated: Use it to fetch every unlocked repository and list the commits upgrade would apply, without applying them.");
+        println!("      | Examples:");
+        println!("      | lubig outdated");
+        println!("      | lubig outdated --json");
         
         // Build a registered repository
         println!("  build: Use it to compile, build or rebuild a specific registered repository.");
-        println!("      | Example:");
+        println!("      | Runs profiles/<name>.sh (.bat on Windows), or the steps of a declarative profiles/<name>.toml.");
+        println!("      | The script builds into a staging directory; the previous build is only replaced when it succeeds.");
+        println!("      | Skipped when the commit and the profile script haven't changed since the last build; --force rebuilds anyway.");
+        println!("      | Examples:");
         println!("      | lubig build <registered_repository_name>");
+        println!("      | lubig build <registered_repository_name> --force");
+        
+        // Profile templates
+        println!("  profile init: Use it to write a ready-to-edit build script for a repository into the profiles directory.");
+        println!("      | Templates: cargo, cmake, meson, make, autotools, go, npm. Without --template the build system is detected");
+        println!("      | from the source tree (Cargo.toml, CMakeLists.txt, meson.build, Makefile, configure, go.mod, package.json), or a skeleton is written.");
+        println!("      | Example:");
+        println!("      | lubig profile init <registered_repository_name> (optional)--template <cargo | cmake | meson | make | autotools | go | npm>");
+        
+        // Build logs
+        println!("  logs: Use it to read the build logs of a repository, kept in logs/<name>/ next to the config file.");
+        println!("      | Examples:");
+        println!("      | lubig logs <registered_repository_name> (optional)--last (the latest build)");
+        println!("      | lubig logs <registered_repository_name> --failed (the latest failed build)");
+        println!("      | lubig logs <registered_repository_name> --list");
+        
+        // Build generations
+        println!("  generations/switch: Use it to list the kept builds of a repository or to make another one current.");
+        println!("      | Every build is installed in <programs>/<name>/<generation> and <programs>/<name>/current points at the active one.");
+        println!("      | Examples:");
+        println!("      | lubig generations <registered_repository_name>");
+        println!("      | lubig switch <registered_repository_name> <generation>");
+        
+        // Roll back the last upgrade
+        println!("  rollback: Use it to restore the commit a repository had before its last upgrade (rebuilds it and locks it).");
+        println!("      | Examples:");
+        println!("      | lubig rollback <registered_repository_name>");
+        println!("      | lubig rollback <registered_repository_name> --steps <N>");
         
         // Remove a registered repository
         println!("  remove: Use it to delete a registered repository and its builds.");
@@ -59,6 +143,19 @@ impl Text
         println!("      | Example:");
         println!("      | lubig status <registered_repository_name>");
         
+        // Reproduce the pinned workspace
+        println!("  sync: Use it to check out every repository at the exact commit recorded in lubig.lock (missing ones are cloned).");
+        println!("      | lubig.lock is rewritten after every get, upgrade and build.");
+        println!("      | Example:");
+        println!("      | lubig sync --locked");
+        
+        // Move a workspace between machines
+        println!("  export/import: Use it to save the workspace to a portable manifest or recreate it on another machine.");
+        println!("      | Import clones missing repositories, installs their profiles and restores lock state; existing ones are skipped.");
+        println!("      | Examples:");
+        println!("      | lubig export <file.toml> (optional)--reference (reference profile scripts, shipped next to the manifest, instead of inlining them)");
+        println!("      | lubig import <file.toml>");
+        
         // External reference to user manual
         println!("  For more information, refer to the lubig User Manual available on GitHub: https://github.com/GrayDay-git/lubig");
         println!("(END)");
@@ -98,4 +195,279 @@ impl Text
     pub fn error_dir(path:&str){
         println!("ERROR: '{}' this path doesn't exists", path)
     }
-}
\ No newline at end of file
+
+    /// Error when `config.toml` can't be loaded.
+    pub fn config_error(err: &str){
+        println!("ERROR: Unable to load the configuration. {}", err);
+        println!("       Nothing was changed. Fix or restore the file before running lubig again.");
+    }
+
+    /// Error when `config.toml` can't be written.
+    pub fn save_error(err: &str){
+        println!("ERROR: Unable to save the configuration. {}", err);
+    }
+
+    /// Prints the resolved workspace location and configured directories.
+    pub fn conf_show(home: &Home, config: &Config) {
+        let show = |val: &Option<String>| val.clone().unwrap_or_else(|| "(unset)".into());
+
+        println!("config:   {} (from {})", home.config.display(), home.origin);
+        println!("sources:  {}", show(&config.directories.sources));
+        println!("profiles: {}", show(&config.directories.profiles));
+        println!("programs: {}", show(&config.directories.programs));
+        println!("keep:     {} build generation(s) per program", config.builds.keep);
+        println!("logs:     {} build log(s) per repository", config.builds.logs);
+    }
+
+    /// Reports the build system detected in a newly registered repository without a profile.
+    pub fn detected_build(name: &str, detected: Option<(&str, &str)>) {
+        match detected {
+            Some((kind, file)) => {
+                println!("Detected {} build system ({}). Create a matching profile with:", kind, file);
+                println!("      | lubig profile init {} --template {}", name, kind);
+            }
+            None => {
+                println!("No known build system detected. Write a profile before building, e.g.:");
+                println!("      | lubig profile init {}", name);
+            }
+        }
+    }
+
+    /// Error when `lubig.lock` can't be loaded.
+    pub fn lockfile_error(err: &str){
+        println!("ERROR: Unable to load lubig.lock. {}", err);
+    }
+
+    /// Enables or disables progress lines.
+    pub fn set_progress(enabled: bool) {
+        PROGRESS.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Renders git transfer progress on a single, continuously rewritten stderr line.
+    pub fn transfer_progress(received: usize, total: usize, indexed_deltas: usize, total_deltas: usize, bytes: usize) {
+        if total == 0 || !PROGRESS.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if received < total {
+            eprint!("\r  Receiving objects: {:3}% ({}/{}), {} KiB   ", received * 100 / total, received, total, bytes / 1024);
+        } else if let Some(percent) = (indexed_deltas * 100).checked_div(total_deltas) {
+            eprint!("\r  Resolving deltas: {:3}% ({}/{})            ", percent, indexed_deltas, total_deltas);
+        }
+        io::stderr().flush().ok();
+    }
+
+    /// Renders checkout progress on the same line as the transfer progress.
+    pub fn checkout_progress(current: usize, total: usize) {
+        if total == 0 || !PROGRESS.load(Ordering::Relaxed) {
+            return;
+        }
+
+        eprint!("\r  Checking out files: {:3}% ({}/{})            ", current * 100 / total, current, total);
+        io::stderr().flush().ok();
+    }
+
+    /// Terminates a progress line so following output starts on a fresh line.
+    pub fn end_progress() {
+        if !PROGRESS.load(Ordering::Relaxed) {
+            return;
+        }
+        eprint!("\r{:60}\r", "");
+        io::stderr().flush().ok();
+    }
+
+    /// Reports the result of fast-forwarding a repository during upgrade.
+    /// `branch` is the tracked branch, or the tag pattern in tag mode.
+    pub fn pull_outcome(name: &str, branch: &str, outcome: &PullOutcome) {
+        match outcome {
+            PullOutcome::UpToDate => println!("'{}' is up to date on '{}'", name, branch),
+            PullOutcome::FastForwarded { from, to } => {
+                println!("SUCCESS: '{}' fast-forwarded '{}' {}..{}", name, branch, short(from), short(to));
+            }
+            PullOutcome::Created { to } => {
+                println!("SUCCESS: '{}' created local branch '{}' at {}", name, branch, short(to));
+            }
+            PullOutcome::Diverged { local, remote } => {
+                println!("ERROR: '{}' was not upgraded: local '{}' ({}) and origin ({}) have diverged.", name, branch, short(local), short(remote));
+                println!("       Only fast-forwards are applied. Reconcile the branch manually.");
+            }
+            PullOutcome::Tagged { tag, to } => {
+                println!("SUCCESS: '{}' checked out tag '{}' ({})", name, tag, short(to));
+            }
+            PullOutcome::NoMatchingTag => {
+                println!("ERROR: '{}' was not upgraded: no tag matches '{}'.", name, branch);
+            }
+            PullOutcome::Dirty(files) => {
+                println!("ERROR: '{}' was not upgraded: the working tree has {} modified file(s):", name, files.len());
+                for file in files {
+                    println!("       {}", file);
+                }
+                println!("       Commit, stash or discard these changes first.");
+            }
+        }
+    }
+
+    /// Prints the table ending an upgrade run: one row per repository, then totals.
+    pub fn upgrade_summary(rows: &[(String, UpgradeStatus)]) {
+        if rows.is_empty() {
+            println!("No registered repositories to upgrade.");
+            return;
+        }
+
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let mut counts = [0; 7];
+
+        println!("Upgrade summary:");
+        for (name, status) in rows {
+            let (slot, label, detail) = match status {
+                UpgradeStatus::Upgraded(detail)    => (0, "upgraded", detail.as_str()),
+                UpgradeStatus::UpToDate            => (1, "up to date", ""),
+                UpgradeStatus::Diverged            => (2, "diverged", "reconcile the branch manually"),
+                UpgradeStatus::Refused(reason)     => (3, "refused", reason.as_str()),
+                UpgradeStatus::Skipped(reason)     => (4, "skipped", reason.as_str()),
+                UpgradeStatus::FetchFailed(reason) => (5, "FETCH FAILED", reason.as_str()),
+                UpgradeStatus::BuildFailed(reason) => (6, "BUILD FAILED", reason.as_str()),
+            };
+            counts[slot] += 1;
+            println!("  {:width$}  {:12}  {}", name, label, detail, width = width);
+        }
+        println!(
+            "{} upgraded, {} up to date, {} diverged, {} refused, {} skipped, {} fetch failed, {} build failed",
+            counts[0], counts[1], counts[2], counts[3], counts[4], counts[5], counts[6]
+        );
+    }
+
+    /// Lists the build logs of a repository, newest first.
+    pub fn build_logs(name: &str, entries: &[LogEntry]) {
+        if entries.is_empty() {
+            println!("'{}' has no build logs.", name);
+            return;
+        }
+
+        for entry in entries.iter().rev() {
+            println!("{}  {}  {}", LocalStuff::format_timestamp(entry.started), entry.result, entry.path.display());
+        }
+    }
+
+    /// Lists the build generations of a repository, marking the current one.
+    pub fn generations(name: &str, generations: &[Generation], current: Option<&str>) {
+        if generations.is_empty() {
+            println!("'{}' has no build generations. Use 'lubig build {}' first.", name, name);
+            return;
+        }
+
+        for generation in generations.iter().rev() {
+            let mark = if current == Some(generation.id.as_str()) { "*" } else { " " };
+            let commit = generation.commit.as_deref().map(short_str).unwrap_or_else(|| "unknown".into());
+            let date = generation.built_at.map(LocalStuff::format_timestamp).unwrap_or_else(|| "unknown".into());
+            println!("{} {}  commit {}  built {}", mark, generation.id, commit, date);
+        }
+    }
+
+    /// Prints the pending upstream commits of a repository.
+    pub fn pending(name: &str, branch: &str, pending: &Pending) {
+        match &pending.tag {
+            Some(tag) if pending.local == pending.remote => {
+                println!("'{}' is up to date on '{}' (tag '{}')", name, branch, tag);
+                return;
+            }
+            Some(tag) => {
+                println!("'{}' tracking '{}': would check out tag '{}' (behind {}, ahead {})", name, branch, tag, pending.behind, pending.ahead);
+            }
+            None if pending.behind == 0 => {
+                println!("'{}' is up to date on '{}' (ahead {})", name, branch, pending.ahead);
+                return;
+            }
+            None => println!("'{}' on '{}': behind {}, ahead {}", name, branch, pending.behind, pending.ahead),
+        }
+
+        for commit in &pending.incoming {
+            println!("      | {} {}", short_str(&commit.id), commit.summary);
+        }
+    }
+
+    /// Formats the changelog of an upgrade: commit subjects, authors and changed-file
+    /// counts, grouped by conventional-commit type when the subjects follow it.
+    pub fn format_changelog(entries: &[ChangeEntry]) -> String {
+        let line = |e: &ChangeEntry| format!("      | {} {} ({}, {} file(s))\n", short_str(&e.id), e.summary, e.author, e.files);
+
+        if entries.iter().all(|e| e.kind.is_none()) {
+            return entries.iter().map(line).collect();
+        }
+
+        // Well-known types first, then any other type, then non-conventional subjects.
+        const ORDER: [&str; 4] = ["feat", "fix", "perf", "refactor"];
+        let mut kinds: Vec<&str> = entries.iter().filter_map(|e| e.kind.as_deref()).collect();
+        kinds.sort_by_key(|k| (ORDER.iter().position(|o| o == k).unwrap_or(ORDER.len()), *k));
+        kinds.dedup();
+
+        let mut out = String::new();
+        for kind in kinds.iter().map(|k| Some(*k)).chain([None]) {
+            let group: Vec<&ChangeEntry> = entries.iter().filter(|e| e.kind.as_deref() == kind).collect();
+            if group.is_empty() {
+                continue;
+            }
+
+            out += &format!("    {}:\n", kind.unwrap_or("other"));
+            out.extend(group.into_iter().map(line));
+        }
+        out
+    }
+}
+
+/// Abbreviates a commit id for display.
+fn short(oid: &git2::Oid) -> String {
+    short_str(&oid.to_string())
+}
+
+/// Abbreviates a commit SHA string for display.
+fn short_str(sha: &str) -> String {
+    sha.chars().take(7).collect()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, kind: Option<&str>, summary: &str) -> ChangeEntry {
+        ChangeEntry {
+            id: id.into(),
+            kind: kind.map(String::from),
+            summary: summary.into(),
+            author: "dev".into(),
+            files: 1,
+        }
+    }
+
+    #[test]
+    fn changelog_without_types_is_flat() {
+        let entries = [entry("aaaaaaaaaa", None, "Update README"), entry("bbbbbbbbbb", None, "Tweak flags")];
+
+        assert_eq!(
+            Text::format_changelog(&entries),
+            "      | aaaaaaa Update README (dev, 1 file(s))\n      | bbbbbbb Tweak flags (dev, 1 file(s))\n"
+        );
+    }
+
+    #[test]
+    fn changelog_groups_by_type() {
+        let entries = [
+            entry("1111111111", Some("docs"), "docs: usage"),
+            entry("2222222222", Some("fix"), "fix: crash"),
+            entry("3333333333", None, "Update README"),
+            entry("4444444444", Some("feat"), "feat: logs"),
+            entry("5555555555", Some("fix"), "fix(build): output"),
+        ];
+
+        assert_eq!(Text::format_changelog(&entries), [
+            "    feat:\n",
+            "      | 4444444 feat: logs (dev, 1 file(s))\n",
+            "    fix:\n",
+            "      | 2222222 fix: crash (dev, 1 file(s))\n",
+            "      | 5555555 fix(build): output (dev, 1 file(s))\n",
+            "    docs:\n",
+            "      | 1111111 docs: usage (dev, 1 file(s))\n",
+            "    other:\n",
+            "      | 3333333 Update README (dev, 1 file(s))\n",
+        ].concat());
+    }
+}
@@ -1,7 +1,14 @@
 use std::fs;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::path::{Path, PathBuf};
-use git2::{Repository, FetchOptions};
+use git2::{Repository, FetchOptions, Oid, RemoteCallbacks};
+use std::{cell::RefCell, rc::Rc, thread};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use serde::Serialize;
 
+use crate::auth::{self, CredentialChain, SharedChain};
 use crate::text::Text;
 
 /// Utility functions for local filesystem operations.
@@ -10,6 +17,106 @@ pub struct LocalStuff;
 /// Utility functions for remote Git operations.
 pub struct RemoteStuff;
 
+/// Result of `RemoteStuff::pull_fast_forward`.
+#[derive(Debug)]
+pub enum PullOutcome {
+    UpToDate,                                  // Nothing new upstream
+    FastForwarded { from: Oid, to: Oid },      // Local branch moved forward
+    Created { to: Oid },                       // Local branch didn't exist and was created
+    Diverged { local: Oid, remote: Oid },      // Refused: histories diverged
+    Dirty(Vec<String>),                        // Refused: uncommitted local modifications
+    Tagged { tag: String, to: Oid },           // Tag mode: checked out a newer matching tag
+    NoMatchingTag,                             // Tag mode: no tag matches the pattern
+}
+
+impl PullOutcome {
+    /// Whether the upgrade was refused and left the repository as it was.
+    pub fn is_refused(&self) -> bool {
+        matches!(self, PullOutcome::Diverged { .. } | PullOutcome::Dirty(_) | PullOutcome::NoMatchingTag)
+    }
+}
+
+/// Per-repository result of an upgrade run, reported in the final summary.
+#[derive(Debug)]
+pub enum UpgradeStatus {
+    Upgraded(String),                          // Moved to a new commit (`from..to` or tag)
+    UpToDate,                                  // Nothing new upstream
+    Diverged,                                  // Refused: local and origin histories diverged
+    Refused(String),                           // Refused: dirty worktree or no matching tag
+    Skipped(String),                           // Not upgraded on purpose (locked, --fail-fast)
+    FetchFailed(String),                       // Fetch or checkout error
+    BuildFailed(String),                       // Upgraded, but the rebuild failed
+}
+
+impl UpgradeStatus {
+    /// Whether this result makes `upgrade` exit with a non-zero code (and stops `--fail-fast`).
+    /// Refusals (diverged branch, dirty worktree, no matching tag) need manual attention,
+    /// so they count as failures too.
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, UpgradeStatus::Upgraded(_) | UpgradeStatus::UpToDate | UpgradeStatus::Skipped(_))
+    }
+}
+
+/// Upstream changes not yet applied to a repository (see `lubig outdated`).
+#[derive(Debug, Serialize)]
+pub struct Pending {
+    pub local: String,                         // Local branch tip
+    pub remote: String,                        // Fetched remote tip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,                   // Tag mode: highest matching tag
+    pub ahead: usize,                          // Local commits not upstream
+    pub behind: usize,                         // Upstream commits not applied yet
+    pub incoming: Vec<PendingCommit>,          // Newest first
+}
+
+/// One-line description of an incoming commit.
+#[derive(Debug, Serialize)]
+pub struct PendingCommit {
+    pub id: String,
+    pub summary: String,
+}
+
+/// A commit applied by `upgrade`, as shown in the changelog.
+#[derive(Debug)]
+pub struct ChangeEntry {
+    pub id: String,
+    pub kind: Option<String>,                  // Conventional-commit type (`feat`, `fix`, ...)
+    pub summary: String,
+    pub author: String,
+    pub files: usize,                          // Number of changed files
+}
+
+/// Parses a tag name as a semantic version, ignoring a leading `v`.
+fn tag_version(tag: &str) -> Option<semver::Version> {
+    semver::Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+/// Extracts the type of a conventional-commit subject (`type(scope)!: description`).
+fn conventional_type(summary: &str) -> Option<String> {
+    let (head, _) = summary.split_once(':')?;
+    let kind = head.split('(').next()?.trim_end_matches('!');
+
+    let valid = !kind.is_empty() && kind.chars().all(|c| c.is_ascii_lowercase());
+    valid.then(|| kind.to_string())
+}
+
+/// History-limiting and access options for `get`, persisted in the repository record.
+#[derive(Debug, Default, Clone)]
+pub struct CloneOptions {
+    pub depth: Option<u32>,                    // Shallow clone depth (`--depth N`)
+    pub branch: Option<String>,                // Ref to clone instead of the remote HEAD
+    pub single_branch: bool,                   // Only fetch that one branch
+    pub key: Option<String>,                   // SSH private key for this repository (`--key`)
+}
+
+/// How to reach a repository's remote: shallow depth and SSH keys to offer
+/// (repository key first, then the global one).
+#[derive(Debug, Default, Clone)]
+pub struct Access {
+    pub depth: Option<u32>,
+    pub keys: Vec<PathBuf>,
+}
+
 impl LocalStuff {
     /// Validates whether a given path string is usable for LUBIG operations.
     /// - Rejects empty strings or paths containing invalid characters.
@@ -32,10 +139,10 @@ impl LocalStuff {
 
         if Self::not_empty(path) {
             println!("{} This directory is not empty. Use an empty directory.", path);
-            return false;
+            false
         } else {
             println!("{} is an avalaible path...", path);
-            return true;
+            true
         }
     }
 
@@ -54,7 +161,7 @@ impl LocalStuff {
 
     /// Validates the number of arguments for a command.
     /// Prints an error if too many or too few arguments are provided.
-    pub fn cmd_len(cmd: &Vec<String>, expected: usize) -> bool {
+    pub fn cmd_len(cmd: &[String], expected: usize) -> bool {
         if cmd.len() > expected {
             Text::exceed_args();
             false
@@ -84,6 +191,24 @@ impl LocalStuff {
         Ok(())
     }
 
+    /// Writes a script file and, on Unix systems, marks it executable (`755`).
+    pub fn write_script<P: AsRef<Path>>(path: P, contents: &str) -> std::io::Result<()> {
+        let p = path.as_ref();
+
+        if let Some(parent) = p.parent() {
+            Self::generate_path(parent)?;
+        }
+        fs::write(p, contents)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(p, fs::Permissions::from_mode(0o755))?;
+        }
+
+        Ok(())
+    }
+
     /// Deletes a directory and all its contents.
     pub fn delete_dir<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
         let path_ref = path.as_ref();
@@ -113,6 +238,38 @@ impl LocalStuff {
         Ok(())
     }
 
+    /// Points the symlink `link` at `target` (relative to the link's directory),
+    /// replacing any previous link in a single rename.
+    pub fn point_symlink<P: AsRef<Path>>(link: P, target: &str) -> std::io::Result<()> {
+        let link = link.as_ref();
+        let mut tmp = link.as_os_str().to_owned();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+        fs::remove_file(&tmp).ok();
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(target, &tmp)?;
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_dir(target, &tmp)?;
+
+        fs::rename(&tmp, link).inspect_err(|_| { fs::remove_file(&tmp).ok(); })
+    }
+
+    /// Returns the name a symlink points at, if `link` is a symlink.
+    pub fn symlink_target<P: AsRef<Path>>(link: P) -> Option<String> {
+        fs::read_link(link).ok()?.file_name().map(|n| n.to_string_lossy().into_owned())
+    }
+
+    /// Deletes a file or directory, whichever `path` is.
+    pub fn delete_any<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        match path.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+            Ok(_) => fs::remove_file(path),
+            Err(_) => Ok(()),
+        }
+    }
+
     /// Checks if `child` is a subdirectory of `parent`.
     pub fn is_subdir<P: AsRef<Path>, Q: AsRef<Path>>(parent: P, child: Q) -> bool {
         let parent_abs = parent.as_ref().canonicalize().unwrap();
@@ -121,6 +278,93 @@ impl LocalStuff {
         child_abs.starts_with(&parent_abs)
     }
 
+    /// Writes `data` to a temporary sibling of `path` and renames it over the original,
+    /// so readers never observe a partially written file.
+    pub fn write_atomic<P: AsRef<Path>>(path: P, data: &str) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(data.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&tmp, path).inspect_err(|_| { fs::remove_file(&tmp).ok(); })
+    }
+
+    /// Returns the Git blob hash of a file, used to detect profile script changes.
+    pub fn file_hash<P: AsRef<Path>>(path: P) -> Option<String> {
+        git2::Oid::hash_file(git2::ObjectType::Blob, path).ok().map(|oid| oid.to_string())
+    }
+
+    /// Returns the current time as seconds since the Unix epoch.
+    pub fn timestamp() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// Matches `text` against a shell-style glob (`*` any run, `?` any single character).
+    pub fn glob_match(pattern: &str, text: &str) -> bool {
+        let p: Vec<char> = pattern.chars().collect();
+        let t: Vec<char> = text.chars().collect();
+        let (mut pi, mut ti) = (0, 0);
+        let mut star: Option<(usize, usize)> = None;
+
+        while ti < t.len() {
+            if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+                pi += 1;
+                ti += 1;
+            } else if pi < p.len() && p[pi] == '*' {
+                star = Some((pi, ti));
+                pi += 1;
+            } else if let Some((sp, st)) = star {
+                // Let the last `*` swallow one more character and retry.
+                pi = sp + 1;
+                ti = st + 1;
+                star = Some((sp, st + 1));
+            } else {
+                return false;
+            }
+        }
+
+        p[pi..].iter().all(|c| *c == '*')
+    }
+
+    /// Formats a Unix timestamp as `YYYY-MM-DD HH:MM:SS UTC`.
+    pub fn format_timestamp(secs: u64) -> String {
+        let days = (secs / 86_400) as i64;
+        let rem = secs % 86_400;
+
+        // Civil-from-days (Howard Hinnant's algorithm).
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, rem / 3_600, rem % 3_600 / 60, rem % 60)
+    }
+
+    /// Appends text to a file, creating it (and its directory) if needed.
+    pub fn append<P: AsRef<Path>>(path: P, text: &str) -> std::io::Result<()> {
+        let p = path.as_ref();
+
+        if let Some(parent) = p.parent() {
+            Self::generate_path(parent)?;
+        }
+
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(p)?;
+        file.write_all(text.as_bytes())
+    }
+
     /// Removes a build script file for a given repository.
     /// The extension is `.bat` on Windows and `.sh` on Unix.
     pub fn remove_script(path: &str, name: &str) -> std::io::Result<()> {
@@ -134,26 +378,642 @@ impl LocalStuff {
 
         Ok(())
     }
+
+    /// Runs `task` over `items` on up to `jobs` threads and returns the results in input order.
+    pub fn parallel<T: Sync, R: Send>(items: &[T], jobs: usize, task: impl Fn(&T) -> R + Sync) -> Vec<R> {
+        let next = AtomicUsize::new(0);
+        let workers = jobs.clamp(1, items.len().max(1));
+
+        let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else { break; };
+                        done.push((i, task(item)));
+                    }
+                    done
+                }))
+                .collect();
+
+            handles.into_iter()
+                .flat_map(|h| h.join().expect("upgrade worker panicked"))
+                .collect()
+        });
+
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, r)| r).collect()
+    }
 }
 
 impl RemoteStuff {
-    /// Performs a fast-forward pull from the remote `origin` for a given branch.
-    /// - Opens the repository at `path`.
-    /// - Fetches the latest commits for the branch.
-    /// - Checks out the fetched commit and updates HEAD.
-    pub fn pull_fast_forward(path: &str, branch: &str) -> Result<(), git2::Error> {
-        let repo = Repository::open(path)?;
+    /// Builds remote callbacks that render transfer progress on a single line
+    /// and answer credential requests from `chain`.
+    fn callbacks<'a>(chain: &SharedChain) -> RemoteCallbacks<'a> {
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.transfer_progress(|stats| {
+            Text::transfer_progress(
+                stats.received_objects(),
+                stats.total_objects(),
+                stats.indexed_deltas(),
+                stats.total_deltas(),
+                stats.received_bytes(),
+            );
+            true
+        });
+
+        let chain = chain.clone();
+        callbacks.credentials(move |url, username, allowed| auth::credentials(&chain, url, username, allowed));
+        callbacks
+    }
 
+    /// Builds fetch options for `access`: progress, credentials and shallow depth.
+    /// The returned chain records which credentials were offered.
+    pub fn fetch_options<'a>(access: &Access) -> (FetchOptions<'a>, SharedChain) {
+        let chain: SharedChain = Rc::new(RefCell::new(CredentialChain::new(access.keys.clone())));
+
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(Self::callbacks(&chain));
+        if let Some(depth) = access.depth {
+            options.depth(depth.min(i32::MAX as u32) as i32);
+        }
+        (options, chain)
+    }
+
+    /// Fetches `refspecs` from `origin`, explaining authentication failures.
+    fn fetch(repo: &Repository, refspecs: &[&str], access: &Access, all_tags: bool) -> Result<(), git2::Error> {
         let mut remote = repo.find_remote("origin")?;
-        remote.fetch(&[branch], Some(&mut FetchOptions::new()), None)?;
+        let (mut options, chain) = Self::fetch_options(access);
+        if all_tags {
+            options.download_tags(git2::AutotagOption::All);
+        }
+
+        let result = remote.fetch(refspecs, Some(&mut options), None);
+        Text::end_progress();
+        result.map_err(|e| auth::explain(e, &chain))
+    }
+
+    /// Clones `url` into `path` natively (no `git` executable required),
+    /// reporting transfer and checkout progress.
+    /// Honors `--depth`, `--branch` and `--single-branch`.
+    pub fn clone<P: AsRef<Path>>(url: &str, path: P, opts: &CloneOptions, access: &Access) -> Result<Repository, git2::Error> {
+        let mut checkout = CheckoutBuilder::new();
+        checkout.progress(|_, current, total| Text::checkout_progress(current, total));
+
+        let (options, chain) = Self::fetch_options(access);
+        let mut builder = RepoBuilder::new();
+        builder
+            .fetch_options(options)
+            .with_checkout(checkout);
+
+        let branch = match (&opts.branch, opts.single_branch) {
+            (Some(branch), _) => Some(branch.clone()),
+            (None, true) => Some(Self::default_branch(url, access)?),
+            (None, false) => None,
+        };
+
+        if let Some(branch) = &branch {
+            builder.branch(branch);
+        }
+
+        // Restrict the remote's refspec so later fetches stay on one branch too.
+        if let (true, Some(branch)) = (opts.single_branch, branch) {
+            builder.remote_create(move |repo, name, url| {
+                let refspec = format!("+refs/heads/{0}:refs/remotes/{1}/{0}", branch, name);
+                repo.remote_with_fetch(name, url, &refspec)
+            });
+        }
+
+        let result = builder.clone(url, path.as_ref());
+
+        Text::end_progress();
+        result.map_err(|e| auth::explain(e, &chain))
+    }
+
+    /// Returns the branch name the remote's HEAD points to.
+    pub fn default_branch(url: &str, access: &Access) -> Result<String, git2::Error> {
+        let chain: SharedChain = Rc::new(RefCell::new(CredentialChain::new(access.keys.clone())));
+        let mut remote = git2::Remote::create_detached(url)?;
+        remote.connect_auth(git2::Direction::Fetch, Some(Self::callbacks(&chain)), None)
+            .map_err(|e| auth::explain(e, &chain))?;
+        let head = remote.default_branch()?;
+
+        let head = head.as_str().unwrap_or_default();
+        Ok(head.strip_prefix("refs/heads/").unwrap_or(head).to_string())
+    }
+
+    /// Fetches `branch` from `origin` (keeping shallow clones at their depth)
+    /// and returns the fetched tip.
+    pub fn fetch_branch<'r>(repo: &'r Repository, branch: &str, access: &Access) -> Result<git2::AnnotatedCommit<'r>, git2::Error> {
+        Self::fetch(repo, &[branch], access, false)?;
 
         let fetch_head = repo.find_reference("FETCH_HEAD")?;
-        let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
-        let commit = repo.find_commit(fetch_commit.id())?;
+        repo.reference_to_annotated_commit(&fetch_head)
+    }
 
-        repo.checkout_tree(commit.as_object(), None)?;
-        repo.set_head(&format!("refs/heads/{}", branch))?;
+    /// Fetches `branch` and compares the local branch (or HEAD, if the branch doesn't
+    /// exist locally) with the fetched tip, without touching the worktree.
+    pub fn pending(path: &str, branch: &str, access: &Access) -> Result<Pending, git2::Error> {
+        let repo = Repository::open(path)?;
+        let remote = Self::fetch_branch(&repo, branch, access)?.id();
+
+        let local = match repo.find_reference(&format!("refs/heads/{}", branch)) {
+            Ok(r) => r.peel_to_commit()?.id(),
+            Err(_) => repo.head()?.peel_to_commit()?.id(),
+        };
+        Self::deepen_to(&repo, branch, local, remote, access)?;
+        Self::pending_between(&repo, local, remote)
+    }
+
+    /// A shallow fetch cuts the history of the new tip at `depth`, so when more commits than
+    /// that arrived upstream the tip no longer reaches `local` and looks diverged.
+    /// Deepens the fetch of `branch`, doubling the depth each time, until `remote` reaches
+    /// `local` or the whole history is present (a real divergence).
+    fn deepen_to(repo: &Repository, branch: &str, local: Oid, remote: Oid, access: &Access) -> Result<(), git2::Error> {
+        let Some(mut depth) = access.depth else {
+            return Ok(());
+        };
 
+        // libgit2 reads the largest depth as "unshallow": the whole history.
+        let full = i32::MAX as u32;
+        while local != remote && depth < full && repo.is_shallow() && !repo.graph_descendant_of(remote, local)? {
+            // Past a thousand commits, fetch the rest of the history in one go.
+            depth = if depth >= 1024 { full } else { depth * 2 };
+            Self::fetch(repo, &[branch], &Access { depth: Some(depth), ..access.clone() }, false)?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Fetches tags and compares HEAD with the highest tag matching `pattern`.
+    /// Returns `None` when no tag matches.
+    pub fn pending_tag(path: &str, pattern: &str, access: &Access) -> Result<Option<Pending>, git2::Error> {
+        let repo = Repository::open(path)?;
+        Self::fetch_tags(&repo, access)?;
+
+        let Some((tag, remote)) = Self::best_tag(&repo, pattern)? else {
+            return Ok(None);
+        };
+        let local = repo.head()?.peel_to_commit()?.id();
+
+        let mut pending = Self::pending_between(&repo, local, remote)?;
+        pending.tag = Some(tag);
+        Ok(Some(pending))
+    }
+
+    /// Computes ahead/behind counts and the incoming commits from `local` to `remote`.
+    fn pending_between(repo: &Repository, local: Oid, remote: Oid) -> Result<Pending, git2::Error> {
+        let (ahead, behind) = repo.graph_ahead_behind(local, remote)?;
+
+        // Incoming commits: reachable from the remote tip but not from the local one.
+        let mut walk = repo.revwalk()?;
+        walk.push(remote)?;
+        walk.hide(local)?;
+
+        let mut incoming = Vec::new();
+        for oid in walk {
+            let commit = repo.find_commit(oid?)?;
+            incoming.push(PendingCommit {
+                id: commit.id().to_string(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+            });
+        }
+
+        Ok(Pending { local: local.to_string(), remote: remote.to_string(), tag: None, ahead, behind, incoming })
+    }
+
+    /// Fetches every tag from `origin`.
+    pub fn fetch_tags(repo: &Repository, access: &Access) -> Result<(), git2::Error> {
+        Self::fetch(repo, &["+refs/tags/*:refs/tags/*"], access, true)
+    }
+
+    /// Returns the highest tag matching `pattern` and the commit it points to.
+    /// `pattern` is a semver range (`^1.4`, `>=2, <3`) or a glob (`v*`).
+    /// Tags are ordered by semantic version when they parse as one (an optional
+    /// leading `v` is ignored), otherwise by name.
+    pub fn best_tag(repo: &Repository, pattern: &str) -> Result<Option<(String, Oid)>, git2::Error> {
+        let range = Self::semver_range(pattern);
+        let names = repo.tag_names(None)?;
+
+        let best = names.iter()
+            .flatten()
+            .filter(|tag| match &range {
+                Some(req) => tag_version(tag).is_some_and(|v| req.matches(&v)),
+                None => LocalStuff::glob_match(pattern, tag),
+            })
+            .max_by(|a, b| (tag_version(a), *a).cmp(&(tag_version(b), *b)));
+
+        match best {
+            Some(tag) => {
+                let commit = repo.revparse_single(&format!("refs/tags/{}", tag))?.peel_to_commit()?;
+                Ok(Some((tag.to_string(), commit.id())))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Parses `pattern` as a semver range, unless it looks like a glob.
+    pub fn semver_range(pattern: &str) -> Option<semver::VersionReq> {
+        if pattern.contains(['*', '?']) && !pattern.starts_with(['^', '~', '<', '>', '=']) {
+            return None;
+        }
+        semver::VersionReq::parse(pattern).ok()
+    }
+
+    /// Moves a tag-tracking repository to the highest tag matching `pattern`
+    /// (detached HEAD). Refuses when the worktree has local modifications.
+    pub fn upgrade_to_tag(path: &str, pattern: &str, access: &Access) -> Result<PullOutcome, git2::Error> {
+        let repo = Repository::open(path)?;
+        Self::fetch_tags(&repo, access)?;
+
+        let Some((tag, target)) = Self::best_tag(&repo, pattern)? else {
+            return Ok(PullOutcome::NoMatchingTag);
+        };
+
+        if repo.head()?.peel_to_commit()?.id() == target {
+            return Ok(PullOutcome::UpToDate);
+        }
+
+        let dirty = Self::dirty_files(&repo)?;
+        if !dirty.is_empty() {
+            return Ok(PullOutcome::Dirty(dirty));
+        }
+
+        let commit = repo.find_commit(target)?;
+        repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        repo.set_head_detached(target)?;
+
+        Ok(PullOutcome::Tagged { tag, to: target })
+    }
+
+    /// Returns the tag pointing at the currently checked-out commit, if any.
+    pub fn head_tag(path: &str) -> Option<String> {
+        let repo = Repository::open(path).ok()?;
+        let head = repo.head().ok()?.peel_to_commit().ok()?.id();
+        let names = repo.tag_names(None).ok()?;
+
+        names.iter()
+            .flatten()
+            .filter(|tag| {
+                repo.revparse_single(&format!("refs/tags/{}", tag))
+                    .and_then(|o| o.peel_to_commit())
+                    .is_ok_and(|c| c.id() == head)
+            })
+            .max_by(|a, b| (tag_version(a), *a).cmp(&(tag_version(b), *b)))
+            .map(str::to_string)
+    }
+
+    /// Lists the commits reachable from `to` but not from `from`, newest first,
+    /// with their author and number of changed files.
+    pub fn changelog(path: &str, from: &str, to: &str) -> Result<Vec<ChangeEntry>, git2::Error> {
+        let repo = Repository::open(path)?;
+
+        let mut walk = repo.revwalk()?;
+        walk.push(Oid::from_str(to)?)?;
+        walk.hide(Oid::from_str(from)?)?;
+
+        let mut entries = Vec::new();
+        for oid in walk {
+            let commit = repo.find_commit(oid?)?;
+            let summary = commit.summary().unwrap_or_default().to_string();
+
+            // Shallow clones may lack the parent; diff against an empty tree then.
+            let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+            let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+            entries.push(ChangeEntry {
+                id: commit.id().to_string(),
+                kind: conventional_type(&summary),
+                author: commit.author().name().unwrap_or_default().to_string(),
+                files: diff.deltas().len(),
+                summary,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Performs a true fast-forward pull from the remote `origin` for a given branch.
+    /// - Opens the repository at `path`.
+    /// - Fetches the latest commits for the branch (keeping shallow clones at their depth).
+    /// - Refuses when the local branch (or a detached HEAD) diverged or the worktree has local modifications.
+    /// - Moves (or creates) `refs/heads/<branch>`, checks it out and attaches HEAD to it.
+    pub fn pull_fast_forward(path: &str, branch: &str, access: &Access) -> Result<PullOutcome, git2::Error> {
+        let repo = Repository::open(path)?;
+        let fetch_commit = Self::fetch_branch(&repo, branch, access)?;
+        let target = fetch_commit.id();
+        let refname = format!("refs/heads/{}", branch);
+
+        let head_on_branch = repo.head().ok().and_then(|h| h.name().map(|n| n == refname)).unwrap_or(false);
+        let local = repo.find_reference(&refname).ok();
+        let from = local.as_ref().and_then(|r| r.target());
+        if let Some(from) = from {
+            Self::deepen_to(&repo, branch, from, target, access)?;
+        }
+
+        // Decide what to do before touching the worktree.
+        let outcome = match &local {
+            None => PullOutcome::Created { to: target },
+            Some(local) => {
+                let (analysis, _) = repo.merge_analysis_for_ref(local, &[&fetch_commit])?;
+
+                if analysis.is_up_to_date() {
+                    PullOutcome::UpToDate
+                } else if analysis.is_fast_forward() {
+                    PullOutcome::FastForwarded { from: from.unwrap_or(target), to: target }
+                } else {
+                    return Ok(PullOutcome::Diverged { local: from.unwrap_or(target), remote: target });
+                }
+            }
+        };
+
+        // A detached HEAD (after `pin`, `rollback` or tag mode) may hold commits of its own:
+        // only move it to the branch when the new tip already contains it.
+        if repo.head_detached()? {
+            let head = repo.head()?.peel_to_commit()?.id();
+            Self::deepen_to(&repo, branch, head, target, access)?;
+            if head != target && !repo.graph_descendant_of(target, head)? {
+                return Ok(PullOutcome::Diverged { local: head, remote: target });
+            }
+        }
+
+        if matches!(outcome, PullOutcome::UpToDate) && head_on_branch {
+            return Ok(outcome);
+        }
+
+        let dirty = Self::dirty_files(&repo)?;
+        if !dirty.is_empty() {
+            return Ok(PullOutcome::Dirty(dirty));
+        }
+
+        // Check out the new tree first, so a failed checkout never moves the branch.
+        let new_tip = match outcome {
+            PullOutcome::UpToDate => from.unwrap_or(target),
+            _ => target,
+        };
+        let commit = repo.find_commit(new_tip)?;
+        repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+
+        match local {
+            Some(mut local) => { local.set_target(new_tip, &format!("lubig: fast-forward {} to {}", branch, new_tip))?; }
+            None => { repo.branch(branch, &commit, false)?; }
+        }
+        repo.set_head(&refname)?;
+
+        Ok(outcome)
+    }
+
+    /// Lists tracked files with uncommitted modifications (untracked files are ignored).
+    pub fn dirty_files(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+        let mut opts = git2::StatusOptions::new();
+        opts.include_untracked(false).include_ignored(false);
+
+        let statuses = repo.statuses(Some(&mut opts))?;
+        Ok(statuses.iter()
+            .filter(|s| s.status() != git2::Status::CURRENT)
+            .filter_map(|s| s.path().map(str::to_string))
+            .collect())
+    }
+
+    /// Returns the SHA of the commit currently checked out in the repository at `path`.
+    pub fn head_commit<P: AsRef<Path>>(path: P) -> Result<String, git2::Error> {
+        let repo = Repository::open(path)?;
+        let commit = repo.head()?.peel_to_commit()?;
+
+        Ok(commit.id().to_string())
+    }
+
+    /// Checks out an exact commit (detached HEAD) in the repository at `path`.
+    /// Fetches from `origin` first if the commit isn't available locally.
+    /// Refuses to overwrite local modifications.
+    pub fn checkout_commit(path: &str, sha: &str, access: &Access) -> Result<(), git2::Error> {
+        let repo = Repository::open(path)?;
+        let oid = git2::Oid::from_str(sha)?;
+
+        if repo.find_commit(oid).is_err() {
+            Self::fetch_all(&repo, access)?;
+        }
+
+        let commit = repo.find_commit(oid)?;
+        repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        repo.set_head_detached(oid)?;
+
+        Ok(())
+    }
+
+    /// Fetches every branch and tag from `origin`.
+    pub fn fetch_all(repo: &Repository, access: &Access) -> Result<(), git2::Error> {
+        Self::fetch(repo, &["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"], access, false)
+    }
+
+    /// Resolves a commit SHA, tag or branch name to a commit in the repository at `path`.
+    /// Remote branches (`origin/<ref>`) are tried too, and `origin` is fetched
+    /// once if nothing matches locally.
+    pub fn resolve_ref(path: &str, reference: &str, access: &Access) -> Result<Oid, git2::Error> {
+        let repo = Repository::open(path)?;
+        let candidates = [
+            reference.to_string(),
+            format!("refs/tags/{}", reference),
+            format!("origin/{}", reference),
+        ];
+
+        let lookup = |repo: &Repository| {
+            candidates.iter()
+                .find_map(|c| repo.revparse_single(c).and_then(|o| o.peel_to_commit()).ok())
+                .map(|c| c.id())
+        };
+
+        if let Some(oid) = lookup(&repo) {
+            return Ok(oid);
+        }
+
+        Self::fetch_all(&repo, access)?;
+        lookup(&repo).ok_or_else(|| git2::Error::from_str(&format!("'{}' is not a known commit, tag or branch", reference)))
+    }
+
+    /// Lists the modified files of the repository at `path` (see `dirty_files`).
+    pub fn dirty_files_at(path: &str) -> Result<Vec<String>, git2::Error> {
+        Self::dirty_files(&Repository::open(path)?)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(LocalStuff::glob_match("lubig", "lubig"));
+        assert!(LocalStuff::glob_match("*", "anything"));
+        assert!(LocalStuff::glob_match("*", ""));
+        assert!(LocalStuff::glob_match("nvim-*", "nvim-lsp"));
+        assert!(LocalStuff::glob_match("*-cli", "gh-cli"));
+        assert!(LocalStuff::glob_match("a*b*c", "aXbYbZc"));
+        assert!(LocalStuff::glob_match("v?.?", "v1.2"));
+
+        assert!(!LocalStuff::glob_match("lubig", "lubig2"));
+        assert!(!LocalStuff::glob_match("nvim-*", "nvim"));
+        assert!(!LocalStuff::glob_match("v?.?", "v10.2"));
+        assert!(!LocalStuff::glob_match("a*b*c", "aXbY"));
+    }
+
+    #[test]
+    fn semver_range_leaves_globs_alone() {
+        assert!(RemoteStuff::semver_range("^1.2").is_some());
+        assert!(RemoteStuff::semver_range(">=1.0, <2").is_some());
+        assert!(RemoteStuff::semver_range("1.4.2").is_some());
+
+        assert!(RemoteStuff::semver_range("v1.*").is_none());
+        assert!(RemoteStuff::semver_range("release-?").is_none());
+        assert!(RemoteStuff::semver_range("nightly").is_none());
+    }
+
+    #[test]
+    fn best_tag_orders_by_version() {
+        let dir = std::env::temp_dir().join(format!("lubig-best-tag-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        let sig = git2::Signature::now("lubig", "lubig@localhost").unwrap();
+        let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+        let head = repo.commit(Some("HEAD"), &sig, &sig, "init", &tree, &[]).unwrap();
+        let commit = repo.find_object(head, None).unwrap();
+        for tag in ["v1.2.0", "v1.9.9", "v1.10.0", "v2.0.0-rc.1", "nightly-2024", "nightly-2025"] {
+            repo.tag_lightweight(tag, &commit, false).unwrap();
+        }
+
+        let best = |pattern: &str| RemoteStuff::best_tag(&repo, pattern).unwrap().map(|(tag, _)| tag);
+        assert_eq!(best("^1"), Some("v1.10.0".into()));       // numeric, not lexical, order
+        assert_eq!(best(">=1"), Some("v1.10.0".into()));      // pre-releases aren't picked by a plain range
+        assert_eq!(best("v1.*"), Some("v1.10.0".into()));     // globs still rank versions
+        assert_eq!(best("nightly-*"), Some("nightly-2025".into()));
+        assert_eq!(best("^3"), None);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn conventional_type_parses_subjects() {
+        assert_eq!(conventional_type("feat: add logs"), Some("feat".into()));
+        assert_eq!(conventional_type("fix(build): keep the old output"), Some("fix".into()));
+        assert_eq!(conventional_type("refactor!: drop v1 config"), Some("refactor".into()));
+        assert_eq!(conventional_type("perf(fetch)!: shallow clones"), Some("perf".into()));
+
+        assert_eq!(conventional_type("Update README"), None);
+        assert_eq!(conventional_type("Feat: capitalised"), None);
+        assert_eq!(conventional_type("Merge branch 'main': sync"), None);
+        assert_eq!(conventional_type(": empty type"), None);
+    }
+
+    /// A `git daemon` serving `base`, killed when dropped.
+    /// libgit2's local transport can't fetch shallowly, so shallow tests need a real server.
+    struct Daemon(std::process::Child);
+
+    impl Daemon {
+        fn serve(base: &Path) -> Option<(Daemon, u16)> {
+            let port = std::net::TcpListener::bind("127.0.0.1:0").ok()?.local_addr().ok()?.port();
+
+            // Run `git-daemon` itself: `git daemon` would leave it orphaned when killed.
+            let exec_path = std::process::Command::new("git").arg("--exec-path").output().ok()?.stdout;
+            let exec_path = PathBuf::from(String::from_utf8_lossy(&exec_path).trim());
+            let child = std::process::Command::new(exec_path.join("git-daemon"))
+                .args(["--reuseaddr", "--export-all", "--listen=127.0.0.1"])
+                .arg(format!("--port={}", port))
+                .arg(format!("--base-path={}", base.display()))
+                .arg(base)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()
+                .ok()?;
+            let daemon = Daemon(child);
+
+            for _ in 0..50 {
+                if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                    return Some((daemon, port));
+                }
+                thread::sleep(std::time::Duration::from_millis(100));
+            }
+            None
+        }
+    }
+
+    impl Drop for Daemon {
+        fn drop(&mut self) {
+            self.0.kill().ok();
+            self.0.wait().ok();
+        }
+    }
+
+    /// Commits a one-file tree holding `n` on top of HEAD.
+    fn commit(repo: &Repository, n: u32) -> Oid {
+        let sig = git2::Signature::now("lubig", "lubig@localhost").unwrap();
+        let mut tree = repo.treebuilder(None).unwrap();
+        tree.insert("file", repo.blob(n.to_string().as_bytes()).unwrap(), 0o100644).unwrap();
+        let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+
+        repo.commit(Some("HEAD"), &sig, &sig, &format!("c{}", n), &tree, &parent.iter().collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn shallow_clone_fast_forwards() {
+        let dir = std::env::temp_dir().join(format!("lubig-shallow-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let upstream = Repository::init_bare(dir.join("up.git")).unwrap();
+        upstream.set_head("refs/heads/main").unwrap();
+        (1..=3).for_each(|n| { commit(&upstream, n); });
+
+        let Some((_daemon, port)) = Daemon::serve(&dir) else {
+            eprintln!("skipped: 'git daemon' is not available");
+            return;
+        };
+        let url = format!("git://127.0.0.1:{}/up.git", port);
+        let path = dir.join("clone");
+        let access = Access { depth: Some(1), ..Access::default() };
+        let opts = CloneOptions { depth: Some(1), single_branch: true, ..CloneOptions::default() };
+        RemoteStuff::clone(&url, &path, &opts, &access).unwrap();
+        let path = path.to_string_lossy();
+
+        // More upstream commits than the clone depth.
+        let from = upstream.head().unwrap().target().unwrap();
+        commit(&upstream, 4);
+        let to = commit(&upstream, 5);
+
+        let pending = RemoteStuff::pending(&path, "main", &access).unwrap();
+        assert_eq!((pending.ahead, pending.behind, pending.incoming.len()), (0, 2, 2));
+
+        match RemoteStuff::pull_fast_forward(&path, "main", &access).unwrap() {
+            PullOutcome::FastForwarded { from: f, to: t } => assert_eq!((f, t), (from, to)),
+            other => panic!("expected a fast-forward, got {:?}", other),
+        }
+        assert_eq!(RemoteStuff::head_commit(path.as_ref()).unwrap(), to.to_string());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn detached_head_commits_are_not_abandoned() {
+        let dir = std::env::temp_dir().join(format!("lubig-detached-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let upstream = Repository::init_bare(dir.join("up.git")).unwrap();
+        upstream.set_head("refs/heads/main").unwrap();
+        commit(&upstream, 1);
+
+        let path = dir.join("clone");
+        let access = Access::default();
+        let clone = RemoteStuff::clone(&dir.join("up.git").to_string_lossy(), &path, &CloneOptions::default(), &access).unwrap();
+        let path = path.to_string_lossy();
+
+        // A local commit on a detached HEAD, then a new upstream commit.
+        let head = clone.head().unwrap().target().unwrap();
+        clone.set_head_detached(head).unwrap();
+        let local = commit(&clone, 2);
+        let remote = commit(&upstream, 3);
+
+        match RemoteStuff::pull_fast_forward(&path, "main", &access).unwrap() {
+            PullOutcome::Diverged { local: l, remote: r } => assert_eq!((l, r), (local, remote)),
+            other => panic!("expected a divergence, got {:?}", other),
+        }
+        assert_eq!(RemoteStuff::head_commit(path.as_ref()).unwrap(), local.to_string());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
+